@@ -1,16 +1,125 @@
-use crate::db::{Emotion, GetTracksFilters};
-use crate::tracks::{Album, Lyrics, Track, find_artist_image};
+use crate::db::{Emotion, EqualizerAssignment, GetTracksFilters, ScanSummary, TagEdit};
+use crate::equalizer::EqualizerPreset;
+use crate::output::{self, OutputDevice};
+use crate::playback::Crossfade;
+use crate::players::{Normalization, RestoredSession};
+use crate::queue::{QueueEntry, QueueState, Repeat};
+use crate::tags::{BatchOperation, TagChange, TagEditResult, TagUpdate};
+use crate::tracks::{Album, Dir, Lyrics, ScanError, Track, find_artist_image};
+use crate::watcher::WatchError;
 use crate::{AppState, Error};
 use std::path::PathBuf;
-use tauri::State;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, State};
 
 #[tauri::command]
-pub fn player_set_queue(state: State<AppState, '_>, queue: Vec<PathBuf>) -> Result<(), Error> {
-    state.player.lock().set_queue(queue);
+pub async fn player_set_queue(
+    state: State<AppState, '_>,
+    queue: Vec<QueueEntry>,
+) -> Result<(), Error> {
+    let paths: Vec<PathBuf> = queue.iter().map(|x| x.path.clone()).collect();
+    let info = state.db.get_playback_info(&paths).await?;
+    state.player.lock().set_queue(queue, info);
+
+    Ok(())
+}
+
+// skips tracks already in the queue, returns the queue as it ends up
+#[tauri::command]
+pub async fn player_append(
+    state: State<AppState, '_>,
+    entries: Vec<QueueEntry>,
+) -> Result<QueueState, Error> {
+    let paths: Vec<PathBuf> = entries.iter().map(|x| x.path.clone()).collect();
+    let info = state.db.get_playback_info(&paths).await?;
+    let res = state.player.lock().append(entries, info);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn player_insert_next(
+    state: State<AppState, '_>,
+    entries: Vec<QueueEntry>,
+) -> Result<QueueState, Error> {
+    let paths: Vec<PathBuf> = entries.iter().map(|x| x.path.clone()).collect();
+    let info = state.db.get_playback_info(&paths).await?;
+    let res = state.player.lock().insert_next(entries, info);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn player_remove(state: State<AppState, '_>, indexes: Vec<usize>) -> Result<QueueState, Error> {
+    let res = state.player.lock().remove(indexes)?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn player_move(
+    state: State<AppState, '_>,
+    from: usize,
+    to: usize,
+) -> Result<QueueState, Error> {
+    let res = state.player.lock().move_entry(from, to)?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn player_shuffle(state: State<AppState, '_>, seed: Option<u32>) -> Result<QueueState, Error> {
+    let res = state.player.lock().shuffle(seed);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn player_unshuffle(state: State<AppState, '_>) -> Result<QueueState, Error> {
+    let res = state.player.lock().unshuffle();
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn player_set_repeat(state: State<AppState, '_>, repeat: Option<Repeat>) -> Result<(), Error> {
+    state.player.lock().set_repeat(repeat);
 
     Ok(())
 }
 
+// `None` when the queue ends there
+#[tauri::command]
+pub fn player_get_next_index(state: State<AppState, '_>) -> Result<Option<usize>, Error> {
+    let res = state.player.lock().next_index();
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn player_get_prev_index(state: State<AppState, '_>) -> Result<Option<usize>, Error> {
+    let res = state.player.lock().prev_index();
+
+    Ok(res)
+}
+
+// `None` on the first run or after a reset
+#[tauri::command]
+pub async fn player_restore_session(
+    state: State<AppState, '_>,
+) -> Result<Option<RestoredSession>, Error> {
+    let Some((session, tracks)) = state.db.get_session().await? else {
+        return Ok(None);
+    };
+
+    let paths: Vec<PathBuf> = tracks.iter().map(|x| x.path.clone()).collect();
+    let info = state.db.get_playback_info(&paths).await?;
+    let res = state.player.lock().restore(&session, tracks, info);
+
+    Ok(Some(res))
+}
+
 #[tauri::command]
 pub fn player_goto(state: State<AppState, '_>, index: usize) -> Result<(), Error> {
     state.player.lock().goto(index)?;
@@ -18,11 +127,12 @@ pub fn player_goto(state: State<AppState, '_>, index: usize) -> Result<(), Error
     Ok(())
 }
 
+// positions are in milliseconds, returns the one actually reached
 #[tauri::command]
-pub fn player_seek(state: State<AppState, '_>, elapsed: u64) -> Result<(), Error> {
-    state.player.lock().seek(elapsed)?;
+pub fn player_seek(state: State<AppState, '_>, position: u64) -> Result<u64, Error> {
+    let res = state.player.lock().seek(Duration::from_millis(position))?;
 
-    Ok(())
+    Ok(res.as_millis() as u64)
 }
 
 #[tauri::command]
@@ -67,6 +177,48 @@ pub fn player_set_volume(state: State<AppState, '_>, volume: f32) -> Result<(),
     Ok(())
 }
 
+#[tauri::command]
+pub fn player_set_rate(state: State<AppState, '_>, rate: f32) -> Result<(), Error> {
+    state.player.lock().set_rate(rate);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn player_set_pitch(state: State<AppState, '_>, pitch: f32) -> Result<(), Error> {
+    state.player.lock().set_pitch(pitch);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn player_set_normalization(
+    state: State<AppState, '_>,
+    normalization: Normalization,
+) -> Result<(), Error> {
+    state.player.lock().set_normalization(normalization);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn player_set_crossfade(state: State<AppState, '_>, crossfade: Crossfade) -> Result<(), Error> {
+    state.player.lock().set_crossfade(crossfade);
+
+    Ok(())
+}
+
+// `None` plays tracks without a preset of their own flat
+#[tauri::command]
+pub fn player_set_default_equalizer(
+    state: State<AppState, '_>,
+    name: Option<String>,
+) -> Result<(), Error> {
+    state.player.lock().set_default_equalizer(name);
+
+    Ok(())
+}
+
 #[tauri::command]
 pub fn player_get_arbitrary_tracks(state: State<AppState, '_>) -> Result<Vec<Track>, Error> {
     let res = state.player.lock().arbitrary_tracks.clone();
@@ -74,6 +226,24 @@ pub fn player_get_arbitrary_tracks(state: State<AppState, '_>) -> Result<Vec<Tra
     Ok(res)
 }
 
+#[tauri::command]
+pub fn player_get_output_devices() -> Result<Vec<OutputDevice>, Error> {
+    let res = output::devices()?;
+
+    Ok(res)
+}
+
+// moves both players, `None` follows the system default
+#[tauri::command]
+pub async fn player_set_output_device(
+    state: State<AppState, '_>,
+    device: Option<String>,
+) -> Result<(), Error> {
+    state.output.set_device(device)?;
+
+    Ok(())
+}
+
 #[tauri::command]
 pub fn scrub_player_start(state: State<AppState, '_>) -> Result<(), Error> {
     state.scrub_player.lock().start()?;
@@ -91,11 +261,15 @@ pub fn scrub_player_set_current(
     Ok(())
 }
 
+// positions are in milliseconds, returns the one actually reached
 #[tauri::command]
-pub fn scrub_player_seek(state: State<AppState, '_>, elapsed: u64) -> Result<(), Error> {
-    state.scrub_player.lock().seek(elapsed)?;
+pub fn scrub_player_seek(state: State<AppState, '_>, position: u64) -> Result<u64, Error> {
+    let res = state
+        .scrub_player
+        .lock()
+        .seek(Duration::from_millis(position))?;
 
-    Ok(())
+    Ok(res.as_millis() as u64)
 }
 
 #[tauri::command]
@@ -297,17 +471,191 @@ pub async fn db_set_rules(
 }
 
 #[tauri::command]
-pub async fn db_scan_dirs(state: State<AppState, '_>) -> Result<String, Error> {
-    let dirs = state.db.get_dirs().await?;
-    let res = state.db.scan_dirs(&dirs).await?;
+pub async fn db_get_equalizer_presets(
+    state: State<AppState, '_>,
+) -> Result<Vec<EqualizerPreset>, Error> {
+    let res = state.db.get_equalizer_presets().await?;
 
     Ok(res)
 }
 
+// adds the preset or replaces its bands, which the playing track fades over to
+#[tauri::command]
+pub async fn db_set_equalizer_preset(
+    state: State<AppState, '_>,
+    preset: EqualizerPreset,
+) -> Result<(), Error> {
+    state.db.set_equalizer_preset(&preset).await?;
+    refresh_equalizers(&state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_rename_equalizer_preset(
+    state: State<AppState, '_>,
+    name: String,
+    new_name: String,
+) -> Result<(), Error> {
+    state.db.rename_equalizer_preset(&name, &new_name).await?;
+    refresh_equalizers(&state).await?;
+
+    Ok(())
+}
+
+// tracks and albums set to it go back to the default
 #[tauri::command]
-pub async fn db_set_dirs(state: State<AppState, '_>, dirs: Vec<String>) -> Result<(), Error> {
+pub async fn db_remove_equalizer_preset(
+    state: State<AppState, '_>,
+    name: String,
+) -> Result<(), Error> {
+    state.db.remove_equalizer_preset(&name).await?;
+    refresh_equalizers(&state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_get_equalizer_assignment(
+    state: State<AppState, '_>,
+    hash: String,
+) -> Result<EqualizerAssignment, Error> {
+    let res = state.db.get_equalizer_assignment(&hash).await?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn db_set_track_equalizer(
+    state: State<AppState, '_>,
+    hash: String,
+    preset: Option<String>,
+) -> Result<(), Error> {
+    state.db.set_track_equalizer(&hash, preset.as_ref()).await?;
+    refresh_equalizers(&state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_set_album_equalizer(
+    state: State<AppState, '_>,
+    album: String,
+    preset: Option<String>,
+) -> Result<(), Error> {
+    state
+        .db
+        .set_album_equalizer(&album, preset.as_ref())
+        .await?;
+    refresh_equalizers(&state).await?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_scan_dirs(
+    app: AppHandle,
+    state: State<AppState, '_>,
+) -> Result<ScanSummary, Error> {
+    let dirs = state.db.get_scan_dirs().await?;
+    state.scan_cancel.store(false, Ordering::Relaxed);
+
+    let handle = app.clone();
+    let res = state
+        .db
+        .scan_dirs(&dirs, state.scan_cancel.clone(), move |progress| {
+            _ = handle.emit("scan-progress", progress);
+        })
+        .await?;
+
+    state.analyzer.request(&app);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn db_get_scan_errors(state: State<AppState, '_>) -> Result<Vec<ScanError>, Error> {
+    let res = state.db.get_scan_errors().await?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn db_get_watch_errors(state: State<AppState, '_>) -> Vec<WatchError> {
+    state.watcher.lock().errors()
+}
+
+#[tauri::command]
+pub async fn db_retry_scan_errors(
+    app: AppHandle,
+    state: State<AppState, '_>,
+) -> Result<ScanSummary, Error> {
+    let res = state.db.retry_scan_errors().await?;
+    state.analyzer.request(&app);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub fn db_cancel_scan(state: State<AppState, '_>) -> Result<(), Error> {
+    state.scan_cancel.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_set_dirs(
+    app: AppHandle,
+    state: State<AppState, '_>,
+    dirs: Vec<String>,
+) -> Result<(), Error> {
     state.db.set_dirs(&dirs).await?;
 
+    let dirs = state.db.get_scan_dirs().await?;
+    state.watcher.lock().watch(&app, dirs);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_get_dir_options(state: State<AppState, '_>) -> Result<Vec<Dir>, Error> {
+    let res = state.db.get_dir_options().await?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn db_set_dir_options(
+    app: AppHandle,
+    state: State<AppState, '_>,
+    dir: Dir,
+) -> Result<(), Error> {
+    state.db.set_dir_options(&dir).await?;
+
+    let dirs = state.db.get_scan_dirs().await?;
+    state.watcher.lock().watch(&app, dirs);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn db_get_excludes(state: State<AppState, '_>) -> Result<Vec<String>, Error> {
+    let res = state.db.get_excludes().await?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn db_set_excludes(
+    app: AppHandle,
+    state: State<AppState, '_>,
+    patterns: Vec<String>,
+) -> Result<(), Error> {
+    state.db.set_excludes(&patterns).await?;
+
+    let dirs = state.db.get_scan_dirs().await?;
+    state.watcher.lock().watch(&app, dirs);
+
     Ok(())
 }
 
@@ -348,3 +696,73 @@ pub async fn tracks_find_artist_image(
 
     Ok(res)
 }
+
+#[tauri::command]
+pub async fn tracks_update_tags(
+    app: AppHandle,
+    state: State<AppState, '_>,
+    hash: String,
+    update: TagUpdate,
+) -> Result<Track, Error> {
+    let res = state.db.update_tags(&hash, &update).await?;
+
+    // the rewritten file counts as new to the loudness analysis
+    state.analyzer.request(&app);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn tracks_preview_tag_edit(
+    state: State<AppState, '_>,
+    hashes: Vec<String>,
+    operation: BatchOperation,
+) -> Result<Vec<TagChange>, Error> {
+    let res = state.db.preview_tag_edit(&hashes, &operation).await?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn tracks_apply_tag_edit(
+    app: AppHandle,
+    state: State<AppState, '_>,
+    hashes: Vec<String>,
+    operation: BatchOperation,
+) -> Result<TagEditResult, Error> {
+    let res = state.db.apply_tag_edit(&hashes, &operation).await?;
+    state.analyzer.request(&app);
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn tracks_get_tag_edits(state: State<AppState, '_>) -> Result<Vec<TagEdit>, Error> {
+    let res = state.db.get_tag_edits().await?;
+
+    Ok(res)
+}
+
+#[tauri::command]
+pub async fn tracks_undo_tag_edit(
+    app: AppHandle,
+    state: State<AppState, '_>,
+) -> Result<TagEditResult, Error> {
+    let res = state.db.undo_tag_edit().await?;
+    state.analyzer.request(&app);
+
+    Ok(res)
+}
+
+// the player only knows presets and assignments as they were when the queue was set
+async fn refresh_equalizers(state: &AppState) -> anyhow::Result<()> {
+    let presets = state.db.get_equalizer_presets().await?;
+    let paths = state.player.lock().paths();
+    let info = state.db.get_playback_info(&paths).await?;
+
+    let mut player = state.player.lock();
+    player.set_equalizer_presets(presets);
+    player.update_info(info);
+
+    Ok(())
+}
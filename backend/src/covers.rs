@@ -0,0 +1,165 @@
+use crate::utils;
+use anyhow::Result;
+use image::DynamicImage;
+use image::codecs::jpeg::JpegEncoder;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// longest side in pixels, the track list and the grids pick the one closest to their size
+// NOTE: mirrored by `THUMBNAIL_SIZES` in the frontend
+pub const THUMBNAIL_SIZES: [u32; 2] = [128, 512];
+
+// a scan writes covers well before its tracks are saved, covers this new might be one of those
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+// stored once per image, named after its content, so every track of an album shares the same file
+pub fn save(covers_path: &Path, data: &[u8], media_type: &str) -> Result<PathBuf> {
+    let hash = utils::hash(data);
+    let (_, ext) = media_type.split_once('/').unwrap_or(("image", "jpg"));
+    let path = covers_path.join(format!("{hash}.{ext}"));
+
+    if !path.exists() {
+        write(&path, data)?;
+    }
+
+    let missing: Vec<_> = THUMBNAIL_SIZES
+        .into_iter()
+        .map(|size| (size, thumbnail_path(&path, size)))
+        .filter(|(_, path)| !path.exists())
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(path);
+    }
+
+    let image = image::load_from_memory(data).ok();
+
+    for (size, thumbnail) in missing {
+        // an image the decoder doesn't know is kept as it is, better in full than not at all
+        let bytes = image.as_ref().and_then(|x| encode_thumbnail(x, size).ok());
+        write(&thumbnail, bytes.as_deref().unwrap_or(data))?;
+    }
+
+    Ok(path)
+}
+
+// e.g. "{hash}.png" -> "{hash}-128.jpg"
+pub fn thumbnail_path(cover: &Path, size: u32) -> PathBuf {
+    let stem = cover.file_stem().unwrap_or_default().to_string_lossy();
+
+    cover.with_file_name(format!("{stem}-{size}.jpg"))
+}
+
+// removes covers and thumbnails no track points to, and temp files left by writes cut short
+pub fn collect_garbage(covers_path: &Path, referenced: &[PathBuf]) -> Result<()> {
+    let hashes: HashSet<String> = referenced
+        .iter()
+        .filter_map(|x| x.file_stem())
+        .map(|x| x.to_string_lossy().to_string())
+        .collect();
+
+    let now = SystemTime::now();
+    for entry in fs::read_dir(covers_path)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // covers of older versions were named after the track, those go just the same
+        let hash = name.split(['.', '-']).next().unwrap_or_default();
+
+        // a write that never made it into place, its stem is often one that's still in use
+        let is_temp = name.ends_with(".tmp");
+
+        if !is_temp && hashes.contains(hash) {
+            continue;
+        }
+
+        let modified = entry.metadata()?.modified()?;
+
+        if now.duration_since(modified).unwrap_or_default() < GRACE_PERIOD {
+            continue;
+        }
+
+        _ = fs::remove_file(entry.path());
+    }
+
+    Ok(())
+}
+
+fn encode_thumbnail(image: &DynamicImage, size: u32) -> Result<Vec<u8>> {
+    // smaller ones are only re-encoded, never blown up
+    let image = if image.width().max(image.height()) > size {
+        image.thumbnail(size, size)
+    } else {
+        image.clone()
+    };
+
+    let mut bytes = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut bytes, 85);
+    image.to_rgb8().write_with_encoder(encoder)?;
+
+    Ok(bytes)
+}
+
+// scan workers can save the same image at once, each writes its own file and the last rename wins
+fn write(path: &Path, data: &[u8]) -> Result<()> {
+    let temp = path.with_extension(format!("{:?}.tmp", std::thread::current().id()));
+
+    fs::write(&temp, data)?;
+    fs::rename(&temp, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn touch(path: &Path, age: Duration) {
+        let file = File::create(path).unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn collects_old_temp_files_even_of_covers_in_use() {
+        let dir = std::env::temp_dir().join("meowsic-covers-garbage");
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let old = GRACE_PERIOD * 2;
+        let files = [
+            ("used.png", old),
+            ("used-128.jpg", old),
+            ("used.ThreadId(3).tmp", old),
+            ("used.ThreadId(4).tmp", Duration::ZERO),
+            ("unused.png", old),
+            ("fresh.png", Duration::ZERO),
+        ];
+
+        for (name, age) in files {
+            touch(&dir.join(name), age);
+        }
+
+        collect_garbage(&dir, &[dir.join("used.png")]).unwrap();
+
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|x| x.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+
+        left.sort();
+
+        // the fresh temp file may still be about to be renamed into place
+        assert_eq!(
+            left,
+            [
+                "fresh.png",
+                "used-128.jpg",
+                "used.ThreadId(4).tmp",
+                "used.png"
+            ]
+        );
+    }
+}
@@ -1,15 +1,25 @@
+use crate::covers;
+use crate::equalizer::EqualizerPreset;
+use crate::loudness::{self, Loudness};
+use crate::queue::{Repeat, Session};
+use crate::tags::{self, BatchOperation, TagChange, TagEditResult, TagUpdate};
 use crate::tracks;
-use crate::tracks::{Album, Lyrics, Track};
-use crate::utils;
-use anyhow::Result;
+use crate::tracks::{
+    Album, Dir, FileStat, Lyrics, PlaybackInfo, ReplayGain, ScanDir, ScanError, ScanProgress, Track,
+};
+use anyhow::{Context, Result};
+use globset::Glob;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value as JsonValue, json};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::{Pool, QueryBuilder, Sqlite};
+use sqlx::{Pool, QueryBuilder, Sqlite, SqliteConnection};
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::path::{MAIN_SEPARATOR, Path, PathBuf};
+use std::slice::Chunks;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use zip::write::SimpleFileOptions as ZipFileOptions;
 use zip::{ZipArchive, ZipWriter};
 
@@ -46,7 +56,12 @@ impl Db {
             qb.push(" AND t.artist = ").push_bind(artist);
         }
 
-        qb.push(" ORDER BY t.name ASC");
+        // an album lists in its own order, tracks without numbers after the rest
+        if filters.album.is_some() {
+            qb.push(" ORDER BY COALESCE(t.disc_number, 1), t.track_number IS NULL, t.track_number, t.name ASC");
+        } else {
+            qb.push(" ORDER BY t.name ASC");
+        }
 
         let entries: Vec<TrackRow> = qb.build_query_as().fetch_all(&self.pool).await?;
         let tracks = entries.into_iter().map(Track::from).collect();
@@ -76,42 +91,612 @@ impl Db {
         Ok(track)
     }
 
-    pub async fn scan_dirs(&self, dirs: &[impl AsRef<Path>]) -> Result<String> {
-        let (tracks, errors) = tracks::scan(dirs, &self.covers_path)?;
-        let total = tracks.len() + errors.len();
+    pub async fn get_playback_info(
+        &self,
+        paths: &[PathBuf],
+    ) -> Result<HashMap<PathBuf, PlaybackInfo>> {
+        let mut info = HashMap::new();
 
-        let mut qb = QueryBuilder::new(
-            "INSERT OR IGNORE INTO tracks 
-            (hash, path, name, extension, duration, cover, title, artist, album, album_artist, date, genre) ",
-        );
+        for batch in batches(paths, 1) {
+            let mut qb = QueryBuilder::new(
+                "
+                SELECT t.path, t.duration, t.album, t.track_number, t.track_gain, t.track_peak, t.album_gain, t.album_peak, t.loudness, t.true_peak,
+                    COALESCE(te.preset_name, ae.preset_name) AS equalizer
+                FROM tracks AS t
+                LEFT JOIN track_equalizers AS te ON te.track_hash = t.hash
+                LEFT JOIN album_equalizers AS ae ON ae.album = t.album
+                WHERE t.path IN (",
+            );
 
-        qb.push_values(tracks.into_iter().take(32000), |mut b, track| {
-            b.push_bind(track.hash)
-                .push_bind(track.path.to_string_lossy().to_string())
-                .push_bind(track.name)
-                .push_bind(track.extension)
-                .push_bind(track.duration as i64)
-                .push_bind(track.cover.map(|p| p.to_string_lossy().to_string()))
-                .push_bind(track.title)
-                .push_bind(track.artist)
-                .push_bind(track.album)
-                .push_bind(track.album_artist)
-                .push_bind(track.date)
-                .push_bind(track.genre);
+            let mut separated = qb.separated(", ");
+
+            for path in batch {
+                separated.push_bind(path.to_string_lossy().to_string());
+            }
+
+            qb.push(")");
+
+            let rows: Vec<PlaybackInfoRow> = qb.build_query_as().fetch_all(&self.pool).await?;
+
+            info.extend(rows.into_iter().map(|row| {
+                // measured loudness stands in for missing tags, there's no album measurement
+                // so album mode falls back to it as well
+                let gain = ReplayGain {
+                    track_gain: row
+                        .track_gain
+                        .or(row.loudness.map(|x| loudness::REFERENCE_LOUDNESS - x)),
+                    track_peak: row.track_peak.or(row.true_peak),
+                    album_gain: row.album_gain,
+                    album_peak: row.album_peak,
+                };
+
+                let entry = PlaybackInfo {
+                    gain,
+                    duration: row.duration.try_into().unwrap_or_default(),
+                    album: row.album,
+                    track_number: row.track_number,
+                    equalizer: row.equalizer,
+                };
+
+                (PathBuf::from(row.path), entry)
+            }));
+        }
+
+        Ok(info)
+    }
+
+    pub async fn get_session(&self) -> Result<Option<(Session, Vec<Track>)>> {
+        let row: Option<SessionRow> = sqlx::query_as("SELECT * FROM player_session WHERE id = 0")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let entries: Vec<(String, Option<i64>)> = sqlx::query_as(
+            "SELECT track_hash, original_position FROM queue_tracks ORDER BY position ASC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let original = row.shuffle_seed.map(|_| {
+            let mut original: Vec<(i64, String)> = entries
+                .iter()
+                .filter_map(|(hash, position)| Some(((*position)?, hash.clone())))
+                .collect();
+
+            original.sort();
+            original.into_iter().map(|(_, hash)| hash).collect()
         });
 
+        // tracks removed from the library since are simply not found
+        let tracks: Vec<TrackRow> = sqlx::query_as(
+            "
+            SELECT t.*, r.rules
+            FROM queue_tracks AS q
+            JOIN tracks AS t ON t.hash = q.track_hash
+            LEFT JOIN ruleset AS r ON r.track_hash = t.hash
+            ORDER BY q.position ASC
+            ",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let session = Session {
+            hashes: entries.into_iter().map(|(hash, _)| hash).collect(),
+            original,
+            shuffle_seed: row.shuffle_seed,
+            current: row.current as usize,
+            repeat: row.repeat,
+            position: row.position as u64,
+            volume: row.volume,
+        };
+
+        Ok(Some((
+            session,
+            tracks.into_iter().map(Track::from).collect(),
+        )))
+    }
+
+    pub async fn set_session(&self, session: &Session) -> Result<()> {
         let mut tx = self.pool.begin().await?;
 
-        sqlx::query("DELETE FROM tracks").execute(&mut *tx).await?;
-        qb.build().execute(&mut *tx).await?;
+        sqlx::query("DELETE FROM queue_tracks")
+            .execute(&mut *tx)
+            .await?;
+
+        let original: HashMap<&str, usize> = session
+            .original
+            .iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, hash)| (hash.as_str(), index))
+            .collect();
+
+        let entries: Vec<(usize, &String)> = session.hashes.iter().enumerate().collect();
+
+        for batch in batches(&entries, 3) {
+            let mut qb = QueryBuilder::new(
+                "INSERT OR IGNORE INTO queue_tracks (position, track_hash, original_position) ",
+            );
+
+            qb.push_values(batch, |mut b, (position, hash)| {
+                b.push_bind(*position as i64)
+                    .push_bind(hash.as_str())
+                    .push_bind(original.get(hash.as_str()).map(|x| *x as i64));
+            });
+
+            qb.build().execute(&mut *tx).await?;
+        }
+
+        sqlx::query(
+            "
+            INSERT OR REPLACE INTO player_session (id, current, position, volume, repeat, shuffle_seed)
+            VALUES (0, $1, $2, $3, $4, $5)
+            ",
+        )
+        .bind(session.current as i64)
+        .bind(session.position as i64)
+        .bind(session.volume)
+        .bind(session.repeat)
+        .bind(session.shuffle_seed)
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // tracks with gain tags don't need measuring
+    pub async fn get_unanalyzed_tracks(&self, limit: u32) -> Result<Vec<(String, PathBuf)>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            "SELECT hash, path FROM tracks WHERE analyzed = 0 AND track_gain IS NULL AND album_gain IS NULL LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let tracks = rows
+            .into_iter()
+            .map(|(hash, path)| (hash, PathBuf::from(path)))
+            .collect();
+
+        Ok(tracks)
+    }
+
+    // failed measurements are marked apart (2) so they aren't picked up again, a changed file
+    // (e.g. one that was still being copied) is saved again and that puts it back at 0
+    pub async fn set_loudness(&self, results: &[(String, Option<Loudness>)]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for (hash, loudness) in results {
+            sqlx::query(
+                "UPDATE tracks SET loudness = $1, true_peak = $2, analyzed = $3 WHERE hash = $4",
+            )
+            .bind(loudness.map(|x| x.integrated))
+            .bind(loudness.map(|x| x.true_peak))
+            .bind(if loudness.is_some() { 1 } else { 2 })
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn scan_dirs(
+        &self,
+        dirs: &[ScanDir],
+        cancel: Arc<AtomicBool>,
+        on_progress: impl Fn(&ScanProgress) + Send + Sync + 'static,
+    ) -> Result<ScanSummary> {
+        let rows: Vec<(String, String, i64, i64)> =
+            sqlx::query_as("SELECT hash, path, size, mtime FROM tracks")
+                .fetch_all(&self.pool)
+                .await?;
+
+        let known: HashMap<PathBuf, FileStat> = rows
+            .iter()
+            .map(|(_, path, size, mtime)| {
+                let stat = FileStat {
+                    size: (*size).try_into().unwrap_or_default(),
+                    mtime: (*mtime).try_into().unwrap_or_default(),
+                };
+
+                (PathBuf::from(path), stat)
+            })
+            .collect();
+
+        let known = Arc::new(known);
+
+        let tracks::Scan {
+            tracks,
+            seen,
+            errors,
+        } = {
+            let dirs = dirs.to_vec();
+            let covers_path = self.covers_path.clone();
+            let known = known.clone();
+
+            // probing is blocking file io, keep it off the async runtime's threads
+            // NOTE: nothing is written until the scan finishes, so cancelling leaves the tracks as they were
+            tokio::task::spawn_blocking(move || {
+                tracks::scan(&dirs, &covers_path, &known, &cancel, on_progress)
+            })
+            .await??
+        };
+
+        let removed: Vec<&str> = rows
+            .iter()
+            .filter(|(_, path, ..)| !seen.contains(Path::new(path)))
+            .map(|(hash, ..)| hash.as_str())
+            .collect();
+
+        let updated = tracks
+            .iter()
+            .filter(|x| known.contains_key(&x.path))
+            .count();
+
+        let summary = ScanSummary {
+            added: tracks.len() - updated,
+            updated,
+            removed: removed.len(),
+            errors,
+        };
+
+        let mut tx = self.pool.begin().await?;
+
+        for batch in batches(&removed, 1) {
+            let mut qb: QueryBuilder<Sqlite> =
+                QueryBuilder::new("DELETE FROM tracks WHERE hash IN (");
+            let mut separated = qb.separated(", ");
+
+            for hash in batch {
+                separated.push_bind(*hash);
+            }
+
+            qb.push(")");
+            qb.build().execute(&mut *tx).await?;
+        }
+
+        upsert_tracks(&mut tx, &tracks).await?;
+        replace_scan_errors(&mut tx, &summary.errors).await?;
+
+        tx.commit().await?;
+        _ = self.collect_covers().await;
+
+        Ok(summary)
+    }
+
+    pub async fn get_scan_errors(&self) -> Result<Vec<ScanError>> {
+        let errors: Vec<ScanError> = sqlx::query_as("SELECT * FROM scan_errors ORDER BY path ASC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(errors)
+    }
+
+    // probes just the files that failed during the last scan again
+    pub async fn retry_scan_errors(&self) -> Result<ScanSummary> {
+        // files that are gone since can't be retried, the next scan won't report them either
+        let paths: Vec<PathBuf> = self
+            .get_scan_errors()
+            .await?
+            .into_iter()
+            .map(|x| PathBuf::from(x.path))
+            .filter(|x| x.exists())
+            .collect();
+
+        let covers_path = self.covers_path.clone();
+        let (tracks, errors) =
+            tokio::task::spawn_blocking(move || tracks::probe(&paths, &covers_path)).await??;
+
+        let mut tx = self.pool.begin().await?;
+
+        upsert_tracks(&mut tx, &tracks).await?;
+        replace_scan_errors(&mut tx, &errors).await?;
+
         tx.commit().await?;
 
-        // simple result format to show in the UI
-        Ok(format!(
-            "{}\n\nScanned {total} tracks with {} errors.",
-            errors.join("\n"),
-            errors.len(),
-        ))
+        Ok(ScanSummary {
+            added: tracks.len(),
+            errors,
+            ..ScanSummary::default()
+        })
+    }
+
+    pub async fn sync_changes(&self, changes: &LibraryChanges) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // renamed files and folders keep their playlists, emotions, lyrics and rules,
+        // the renamed rows themselves get their new path from the upsert below
+        let mut rekeyed = Vec::new();
+
+        for (from, to) in &changes.renamed {
+            for (hash, path) in under_path(&mut tx, from).await? {
+                let Ok(rest) = Path::new(&path).strip_prefix(from) else {
+                    continue;
+                };
+
+                let path = to.join(rest);
+                rekeyed.push((hash, tracks::hash_path(&path)));
+            }
+        }
+
+        rekey_tracks(&mut tx, &rekeyed).await?;
+
+        let mut removed = Vec::new();
+
+        for path in &changes.removed {
+            removed.extend(
+                under_path(&mut tx, path)
+                    .await?
+                    .into_iter()
+                    .map(|(hash, _)| hash),
+            );
+        }
+
+        for batch in batches(&removed, 1) {
+            let mut qb: QueryBuilder<Sqlite> =
+                QueryBuilder::new("DELETE FROM tracks WHERE hash IN (");
+            let mut separated = qb.separated(", ");
+
+            for hash in batch {
+                separated.push_bind(hash);
+            }
+
+            qb.push(")");
+            qb.build().execute(&mut *tx).await?;
+        }
+
+        let covers_changed =
+            !removed.is_empty() || covers_changed(&mut tx, &changes.tracks).await?;
+
+        upsert_tracks(&mut tx, &changes.tracks).await?;
+        tx.commit().await?;
+
+        if covers_changed {
+            _ = self.collect_covers().await;
+        }
+
+        Ok(())
+    }
+
+    // writes the tags into the file and reads it back, so the row holds what the file now says
+    pub async fn update_tags(&self, hash: impl AsRef<str>, update: &TagUpdate) -> Result<Track> {
+        let track = self
+            .get_track(hash.as_ref())
+            .await?
+            .context("track not found")?;
+
+        let covers_path = self.covers_path.clone();
+        let update = update.clone();
+        let path = track.path.clone();
+
+        let mut updated = tokio::task::spawn_blocking(move || {
+            tags::write_tags(&path, &update)?;
+            Track::new(&path, &covers_path)
+        })
+        .await??;
+
+        // the row stays the same one, rules only live in the database
+        updated.hash = track.hash;
+        updated.rules = track.rules;
+
+        let mut tx = self.pool.begin().await?;
+        let covers_changed = covers_changed(&mut tx, std::slice::from_ref(&updated)).await?;
+
+        upsert_tracks(&mut tx, std::slice::from_ref(&updated)).await?;
+        tx.commit().await?;
+
+        if covers_changed {
+            _ = self.collect_covers().await;
+        }
+
+        Ok(updated)
+    }
+
+    pub async fn preview_tag_edit(
+        &self,
+        hashes: &[String],
+        operation: &BatchOperation,
+    ) -> Result<Vec<TagChange>> {
+        let tracks = self.get_tracks_by_hash(hashes).await?;
+
+        tags::plan(&tracks, operation)
+    }
+
+    // planned again rather than taken from the preview, the files may have changed since
+    pub async fn apply_tag_edit(
+        &self,
+        hashes: &[String],
+        operation: &BatchOperation,
+    ) -> Result<TagEditResult> {
+        let tracks = self.get_tracks_by_hash(hashes).await?;
+        let changes = tags::plan(&tracks, operation)?;
+        let (result, undo) = self.apply_tag_changes(changes).await?;
+
+        if !undo.is_empty() {
+            sqlx::query("INSERT INTO tag_edits (summary, changes) VALUES ($1, $2)")
+                .bind(operation.summary(undo.len()))
+                .bind(serde_json::to_string(&undo)?)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(result)
+    }
+
+    // newest first, only the newest can be undone
+    pub async fn get_tag_edits(&self) -> Result<Vec<TagEdit>> {
+        let edits: Vec<TagEdit> =
+            sqlx::query_as("SELECT id, summary FROM tag_edits ORDER BY id DESC")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(edits)
+    }
+
+    pub async fn undo_tag_edit(&self) -> Result<TagEditResult> {
+        let row: Option<(i64, String)> =
+            sqlx::query_as("SELECT id, changes FROM tag_edits ORDER BY id DESC LIMIT 1")
+                .fetch_optional(&self.pool)
+                .await?;
+
+        let (id, changes) = row.context("nothing to undo")?;
+        let (result, _) = self
+            .apply_tag_changes(serde_json::from_str(&changes)?)
+            .await?;
+
+        // files that couldn't be put back aren't going to be any time later either
+        sqlx::query("DELETE FROM tag_edits WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result)
+    }
+
+    // file by file, one failing doesn't stop the rest, returns the changes that undo what was done
+    async fn apply_tag_changes(
+        &self,
+        changes: Vec<TagChange>,
+    ) -> Result<(TagEditResult, Vec<TagChange>)> {
+        let hashes: Vec<String> = changes.iter().map(|x| x.hash.clone()).collect();
+        let tracks: HashMap<String, Track> = self
+            .get_tracks_by_hash(&hashes)
+            .await?
+            .into_iter()
+            .map(|x| (x.hash.clone(), x))
+            .collect();
+
+        let covers_path = self.covers_path.clone();
+
+        let (updated, undo, failed) = tokio::task::spawn_blocking(move || {
+            let mut updated = Vec::new();
+            let mut undo = Vec::new();
+            let mut failed = Vec::new();
+
+            for change in changes {
+                let Some(track) = tracks.get(&change.hash) else {
+                    failed.push(TagChange {
+                        error: Some("The track isn't in the library anymore".into()),
+                        ..change
+                    });
+
+                    continue;
+                };
+
+                let path = match change.apply() {
+                    Ok(path) => path,
+                    Err(err) => {
+                        failed.push(TagChange {
+                            error: Some(err.to_string()),
+                            ..change
+                        });
+
+                        continue;
+                    }
+                };
+
+                // the file has changed from here on, so it gets undone even if reading it back
+                // fails, in which case the row is left as it was and still goes by the old hash
+                let undo_hash = match Track::new(&path, &covers_path) {
+                    Ok(mut new) => {
+                        // same as in `update_tags`, only a renamed file gets a new identity
+                        if change.rename.is_none() {
+                            new.hash = track.hash.clone();
+                        }
+
+                        new.rules = track.rules.clone();
+
+                        let hash = new.hash.clone();
+                        updated.push((track.hash.clone(), new));
+                        hash
+                    }
+                    Err(err) => {
+                        failed.push(TagChange {
+                            error: Some(format!("Changed, but couldn't be read back: {err}")),
+                            ..change.clone()
+                        });
+
+                        track.hash.clone()
+                    }
+                };
+
+                undo.push(TagChange {
+                    hash: undo_hash,
+                    ..change.undo(track)
+                });
+            }
+
+            // last to first, so renames unwind in the order they were made
+            undo.reverse();
+
+            (updated, undo, failed)
+        })
+        .await?;
+
+        let rekeyed: Vec<(String, String)> = updated
+            .iter()
+            .filter(|(old, new)| *old != new.hash)
+            .map(|(old, new)| (old.clone(), new.hash.clone()))
+            .collect();
+
+        let updated: Vec<Track> = updated.into_iter().map(|(_, x)| x).collect();
+
+        let mut tx = self.pool.begin().await?;
+
+        rekey_tracks(&mut tx, &rekeyed).await?;
+        let covers_changed = covers_changed(&mut tx, &updated).await?;
+
+        upsert_tracks(&mut tx, &updated).await?;
+        tx.commit().await?;
+
+        if covers_changed {
+            _ = self.collect_covers().await;
+        }
+
+        let result = TagEditResult {
+            applied: updated.len(),
+            failed,
+        };
+
+        Ok((result, undo))
+    }
+
+    // covers are shared between tracks, one goes once the last track showing it changed or is gone
+    // NOTE: only housekeeping, whatever called it went through either way, and it reads the
+    // whole folder, so anything but a scan only calls it when a cover could have been let go
+    async fn collect_covers(&self) -> Result<()> {
+        let referenced: Vec<PathBuf> =
+            sqlx::query_scalar("SELECT DISTINCT cover FROM tracks WHERE cover IS NOT NULL")
+                .fetch_all(&self.pool)
+                .await?
+                .into_iter()
+                .map(|x: String| PathBuf::from(x))
+                .collect();
+
+        let covers_path = self.covers_path.clone();
+        tokio::task::spawn_blocking(move || covers::collect_garbage(&covers_path, &referenced))
+            .await??;
+
+        Ok(())
+    }
+
+    // in the order of `hashes`, ones not in the library are left out
+    async fn get_tracks_by_hash(&self, hashes: &[String]) -> Result<Vec<Track>> {
+        let mut tracks = Vec::with_capacity(hashes.len());
+
+        for hash in hashes {
+            tracks.extend(self.get_track(hash).await?);
+        }
+
+        Ok(tracks)
     }
 
     pub async fn get_playlists(&self) -> Result<Vec<String>> {
@@ -188,19 +773,21 @@ impl Db {
             let mut tx = self.pool.begin().await?;
             let name = name.as_ref();
 
-            let mut qb: QueryBuilder<Sqlite> =
-                QueryBuilder::new("DELETE FROM playlist_tracks WHERE playlist_name = ");
+            for batch in batches(hashes, 1) {
+                let mut qb: QueryBuilder<Sqlite> =
+                    QueryBuilder::new("DELETE FROM playlist_tracks WHERE playlist_name = ");
 
-            qb.push_bind(name);
-            qb.push(" AND track_hash IN (");
-            let mut separated = qb.separated(", ");
+                qb.push_bind(name);
+                qb.push(" AND track_hash IN (");
+                let mut separated = qb.separated(", ");
 
-            for hash in hashes.iter().take(32000) {
-                separated.push_bind(hash.as_ref());
-            }
+                for hash in batch {
+                    separated.push_bind(hash.as_ref());
+                }
 
-            qb.push(")");
-            qb.build().execute(&mut *tx).await?;
+                qb.push(")");
+                qb.build().execute(&mut *tx).await?;
+            }
 
             sqlx::query(
                 "
@@ -272,15 +859,19 @@ impl Db {
             return Ok(());
         }
 
-        let mut qb =
-            QueryBuilder::new("INSERT INTO playlist_tracks (playlist_name, track_hash, position) ");
+        for batch in batches(&filtered, 3) {
+            let mut qb = QueryBuilder::new(
+                "INSERT INTO playlist_tracks (playlist_name, track_hash, position) ",
+            );
 
-        qb.push_values(filtered.into_iter().take(32000), |mut b, hash| {
-            b.push_bind(name).push_bind(hash).push_bind(max_pos);
-            max_pos += 1;
-        });
+            qb.push_values(batch, |mut b, hash| {
+                b.push_bind(name).push_bind(*hash).push_bind(max_pos);
+                max_pos += 1;
+            });
+
+            qb.build().execute(&mut *tx).await?;
+        }
 
-        qb.build().execute(&mut *tx).await?;
         tx.commit().await?;
 
         Ok(())
@@ -483,20 +1074,152 @@ impl Db {
         Ok(())
     }
 
-    pub async fn get_dirs(&self) -> Result<Vec<String>> {
-        let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM dirs ORDER BY path ASC")
+    pub async fn get_equalizer_presets(&self) -> Result<Vec<EqualizerPreset>> {
+        let rows: Vec<EqualizerPresetRow> =
+            sqlx::query_as("SELECT * FROM equalizer_presets ORDER BY name ASC")
+                .fetch_all(&self.pool)
+                .await?;
+
+        let presets = rows.into_iter().map(EqualizerPreset::from).collect();
+
+        Ok(presets)
+    }
+
+    // upsert rather than replace, replacing would cascade and drop every assignment
+    pub async fn set_equalizer_preset(&self, preset: &EqualizerPreset) -> Result<()> {
+        sqlx::query(
+            "
+            INSERT INTO equalizer_presets (name, bands) VALUES ($1, $2)
+            ON CONFLICT(name) DO UPDATE SET bands = excluded.bands
+            ",
+        )
+        .bind(&preset.name)
+        .bind(serde_json::to_string(&preset.bands)?)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn rename_equalizer_preset(
+        &self,
+        name: impl AsRef<str>,
+        new_name: impl AsRef<str>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE equalizer_presets SET name = $1 WHERE name = $2")
+            .bind(new_name.as_ref())
+            .bind(name.as_ref())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_equalizer_preset(&self, name: impl AsRef<str>) -> Result<()> {
+        sqlx::query("DELETE FROM equalizer_presets WHERE name = $1")
+            .bind(name.as_ref())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_equalizer_assignment(
+        &self,
+        hash: impl AsRef<str>,
+    ) -> Result<EqualizerAssignment> {
+        let assignment: Option<EqualizerAssignment> = sqlx::query_as(
+            "
+            SELECT te.preset_name AS track, ae.preset_name AS album
+            FROM tracks AS t
+            LEFT JOIN track_equalizers AS te ON te.track_hash = t.hash
+            LEFT JOIN album_equalizers AS ae ON ae.album = t.album
+            WHERE t.hash = $1
+            ",
+        )
+        .bind(hash.as_ref())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(assignment.unwrap_or_default())
+    }
+
+    // `None` goes back to the album's preset, or the default one
+    pub async fn set_track_equalizer(
+        &self,
+        hash: impl AsRef<str>,
+        preset: Option<impl AsRef<str>>,
+    ) -> Result<()> {
+        match preset {
+            Some(preset) => {
+                sqlx::query("INSERT OR REPLACE INTO track_equalizers (track_hash, preset_name) VALUES ($1, $2)")
+                    .bind(hash.as_ref())
+                    .bind(preset.as_ref())
+                    .execute(&self.pool)
+                    .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM track_equalizers WHERE track_hash = $1")
+                    .bind(hash.as_ref())
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_album_equalizer(
+        &self,
+        album: impl AsRef<str>,
+        preset: Option<impl AsRef<str>>,
+    ) -> Result<()> {
+        match preset {
+            Some(preset) => {
+                sqlx::query(
+                    "INSERT OR REPLACE INTO album_equalizers (album, preset_name) VALUES ($1, $2)",
+                )
+                .bind(album.as_ref())
+                .bind(preset.as_ref())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM album_equalizers WHERE album = $1")
+                    .bind(album.as_ref())
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn get_dirs(&self) -> Result<Vec<String>> {
+        let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM dirs ORDER BY path ASC")
             .fetch_all(&self.pool)
             .await?;
 
         Ok(paths)
     }
 
+    // NOTE: keeps the options of folders that stay in the list
     pub async fn set_dirs(&self, paths: &[impl AsRef<str>]) -> Result<()> {
         let mut tx = self.pool.begin().await?;
-        sqlx::query("DELETE FROM dirs").execute(&mut *tx).await?;
+
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("DELETE FROM dirs WHERE path NOT IN (");
+        let mut separated = qb.separated(", ");
+
+        for path in paths {
+            separated.push_bind(path.as_ref());
+        }
+
+        qb.push(")");
+        qb.build().execute(&mut *tx).await?;
 
         for path in paths {
-            sqlx::query("INSERT INTO dirs (path) VALUES ($1)")
+            sqlx::query("INSERT OR IGNORE INTO dirs (path) VALUES ($1)")
                 .bind(path.as_ref())
                 .execute(&mut *tx)
                 .await?;
@@ -507,9 +1230,112 @@ impl Db {
         Ok(())
     }
 
+    pub async fn get_dir_options(&self) -> Result<Vec<Dir>> {
+        let entries: Vec<DirRow> = sqlx::query_as("SELECT * FROM dirs ORDER BY path ASC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let dirs = entries.into_iter().map(Dir::from).collect();
+
+        Ok(dirs)
+    }
+
+    pub async fn set_dir_options(&self, dir: &Dir) -> Result<()> {
+        // validates the patterns before storing them
+        ScanDir::new(dir, &[])?;
+
+        sqlx::query(
+            "
+            UPDATE dirs
+            SET excludes = $1, follow_links = $2, max_depth = $3, skip_hidden = $4
+            WHERE path = $5
+            ",
+        )
+        .bind(serde_json::to_string(&dir.excludes)?)
+        .bind(dir.follow_links)
+        .bind(dir.max_depth)
+        .bind(dir.skip_hidden)
+        .bind(&dir.path)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    // exclude patterns that apply to every folder
+    pub async fn get_excludes(&self) -> Result<Vec<String>> {
+        let patterns: Vec<String> =
+            sqlx::query_scalar("SELECT pattern FROM excludes ORDER BY pattern ASC")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(patterns)
+    }
+
+    pub async fn set_excludes(&self, patterns: &[impl AsRef<str>]) -> Result<()> {
+        for pattern in patterns {
+            Glob::new(pattern.as_ref())?;
+        }
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM excludes")
+            .execute(&mut *tx)
+            .await?;
+
+        for pattern in patterns {
+            sqlx::query("INSERT OR IGNORE INTO excludes (pattern) VALUES ($1)")
+                .bind(pattern.as_ref())
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    // the configured folders with their options, ready to be scanned or watched
+    pub async fn get_scan_dirs(&self) -> Result<Vec<ScanDir>> {
+        let excludes = self.get_excludes().await?;
+
+        self.get_dir_options()
+            .await?
+            .iter()
+            .map(|x| ScanDir::new(x, &excludes))
+            .collect()
+    }
+
     pub async fn restore(&self, path: impl AsRef<Path>) -> Result<()> {
         let reader = fs::File::open(path)?;
         let mut zip = ZipArchive::new(reader)?;
+        let dirs = self.get_dirs().await?;
+        let mut tx = self.pool.begin().await?;
+
+        let rows: Vec<(String, String, String, String)> =
+            sqlx::query_as("SELECT hash, path, name, extension FROM tracks ORDER BY path ASC")
+                .fetch_all(&mut *tx)
+                .await?;
+
+        let mut by_path: HashMap<String, &str> = HashMap::new();
+        let mut by_name: HashMap<String, &str> = HashMap::new();
+
+        for (hash, path, name, extension) in &rows {
+            let path = Path::new(path);
+            let relative = tracks::relative_path(path, tracks::library_root(path, &dirs));
+
+            by_path.entry(relative).or_insert(hash);
+            by_name.entry(format!("{name}.{extension}")).or_insert(hash);
+        }
+
+        // prefer the path relative to the library folder, but fall back to the file name
+        // when the library is laid out differently on this machine (or the backup predates paths)
+        let resolve = |json: &JsonValue| {
+            json["path"]
+                .as_str()
+                .and_then(|x| by_path.get(x))
+                .or_else(|| json["file_name"].as_str().and_then(|x| by_name.get(x)))
+                .map(|x| x.to_string())
+        };
 
         for i in 0..zip.len() {
             let file = zip.by_index(i)?;
@@ -522,10 +1348,9 @@ impl Db {
                     let mut map = HashMap::new();
 
                     for json in list {
-                        if let (Some(file_name), Some(position)) =
-                            (json["file_name"].as_str(), json["position"].as_i64())
+                        if let (Some(hash), Some(position)) =
+                            (resolve(json), json["position"].as_i64())
                         {
-                            let hash = utils::hash(file_name.as_bytes());
                             map.insert(hash, position);
                         }
                     }
@@ -534,26 +1359,26 @@ impl Db {
                         continue;
                     }
 
-                    // NOTE: doing one transaction per playlist
-                    let mut tx = self.pool.begin().await?;
-
                     sqlx::query("INSERT OR IGNORE INTO playlists (name) VALUES ($1)")
                         .bind(&playlist_name)
                         .execute(&mut *tx)
                         .await?;
 
-                    let mut qb = QueryBuilder::new(
-                        "INSERT OR IGNORE INTO playlist_tracks (playlist_name, track_hash, position) ",
-                    );
+                    let entries: Vec<_> = map.into_iter().collect();
 
-                    qb.push_values(map.iter().take(32000), |mut b, (hash, position)| {
-                        b.push_bind(&playlist_name)
-                            .push_bind(hash)
-                            .push_bind(position);
-                    });
+                    for batch in batches(&entries, 3) {
+                        let mut qb = QueryBuilder::new(
+                            "INSERT OR IGNORE INTO playlist_tracks (playlist_name, track_hash, position) ",
+                        );
+
+                        qb.push_values(batch, |mut b, (hash, position)| {
+                            b.push_bind(&playlist_name)
+                                .push_bind(hash)
+                                .push_bind(position);
+                        });
 
-                    qb.build().execute(&mut *tx).await?;
-                    tx.commit().await?;
+                        qb.build().execute(&mut *tx).await?;
+                    }
                 }
             } else if file.name().starts_with("emotion") {
                 let data: JsonValue = serde_json::from_reader(file)?;
@@ -562,10 +1387,7 @@ impl Db {
                     let mut map = HashMap::new();
 
                     for json in list {
-                        if let (Some(file_name), Some(rank)) =
-                            (json["file_name"].as_str(), json["rank"].as_i64())
-                        {
-                            let hash = utils::hash(file_name.as_bytes());
+                        if let (Some(hash), Some(rank)) = (resolve(json), json["rank"].as_i64()) {
                             map.insert(hash, rank);
                         }
                     }
@@ -574,15 +1396,19 @@ impl Db {
                         continue;
                     }
 
-                    QueryBuilder::new(
-                        "INSERT OR IGNORE INTO emotion_tracks (emotion_name, track_hash, rank) ",
-                    )
-                    .push_values(map.iter().take(32000), |mut b, (hash, rank)| {
-                        b.push_bind(name).push_bind(hash).push_bind(rank);
-                    })
-                    .build()
-                    .execute(&self.pool)
-                    .await?;
+                    let entries: Vec<_> = map.into_iter().collect();
+
+                    for batch in batches(&entries, 3) {
+                        QueryBuilder::new(
+                            "INSERT OR IGNORE INTO emotion_tracks (emotion_name, track_hash, rank) ",
+                        )
+                        .push_values(batch, |mut b, (hash, rank)| {
+                            b.push_bind(name).push_bind(hash).push_bind(rank);
+                        })
+                        .build()
+                        .execute(&mut *tx)
+                        .await?;
+                    }
                 }
             } else if file.name().starts_with("tracks_extended") {
                 let data: Vec<JsonValue> = serde_json::from_reader(file)?;
@@ -590,48 +1416,50 @@ impl Db {
                 let mut lyrics_map: HashMap<String, Lyrics> = HashMap::new();
                 let mut rules_map = HashMap::new();
 
+                // a track can have both
                 for item in data {
-                    match (
-                        item["file_name"].as_str(),
-                        serde_json::from_value(item["lyrics"].clone()),
-                        item["rules"].as_str(),
-                    ) {
-                        (Some(file_name), Err(_), Some(rules)) => {
-                            let hash = utils::hash(file_name.as_bytes());
-                            rules_map.insert(hash, rules.to_string());
-                        }
-                        (Some(file_name), Ok(lyrics), None) => {
-                            let hash = utils::hash(file_name.as_bytes());
-                            lyrics_map.insert(hash, lyrics);
-                        }
-                        _ => {}
+                    let Some(hash) = resolve(&item) else {
+                        continue;
+                    };
+
+                    if let Some(rules) = item["rules"].as_str() {
+                        rules_map.insert(hash.clone(), rules.to_string());
+                    }
+
+                    if let Ok(lyrics) = serde_json::from_value(item["lyrics"].clone()) {
+                        lyrics_map.insert(hash, lyrics);
                     }
                 }
 
-                if !lyrics_map.is_empty() {
+                let lyrics: Vec<_> = lyrics_map.into_iter().collect();
+                let rules: Vec<_> = rules_map.into_iter().collect();
+
+                for batch in batches(&lyrics, 3) {
                     QueryBuilder::new("INSERT OR IGNORE INTO lyrics (track_hash, plain, synced) ")
-                        .push_values(lyrics_map.iter().take(32000), |mut b, (hash, lyrics)| {
+                        .push_values(batch, |mut b, (hash, lyrics)| {
                             b.push_bind(hash)
                                 .push_bind(&lyrics.plain)
                                 .push_bind(&lyrics.synced);
                         })
                         .build()
-                        .execute(&self.pool)
+                        .execute(&mut *tx)
                         .await?;
                 }
 
-                if !rules_map.is_empty() {
+                for batch in batches(&rules, 2) {
                     QueryBuilder::new("INSERT OR IGNORE INTO ruleset (track_hash, rules) ")
-                        .push_values(rules_map.iter().take(32000), |mut b, (hash, rules)| {
+                        .push_values(batch, |mut b, (hash, rules)| {
                             b.push_bind(hash).push_bind(rules);
                         })
                         .build()
-                        .execute(&self.pool)
+                        .execute(&mut *tx)
                         .await?;
                 }
             }
         }
 
+        tx.commit().await?;
+
         Ok(())
     }
 
@@ -642,10 +1470,16 @@ impl Db {
         let file = fs::File::create(&path)?;
         let mut zip = ZipWriter::new(file);
 
+        let dirs = self.get_dirs().await?;
+        let relative = |path: &str| {
+            let path = Path::new(path);
+            tracks::relative_path(path, tracks::library_root(path, &dirs))
+        };
+
         for (index, playlist) in self.get_playlists().await?.iter().enumerate() {
-            let list: Vec<(String, String, i64)> = sqlx::query_as(
+            let list: Vec<(String, String, String, i64)> = sqlx::query_as(
                 "
-                SELECT t.name, t.extension, pt.position
+                SELECT t.path, t.name, t.extension, pt.position
                 FROM tracks AS t
                 JOIN playlist_tracks AS pt ON pt.track_hash = t.hash
                 WHERE pt.playlist_name = $1                
@@ -661,7 +1495,8 @@ impl Db {
 
             let data = json!({
                 "name": playlist,
-                "list": list.into_iter().map(|(name, extension, position)| json!({
+                "list": list.into_iter().map(|(path, name, extension, position)| json!({
+                    "path": relative(&path),
                     "file_name": format!("{name}.{extension}"),
                     "position": position
                 })).collect::<Vec<_>>(),
@@ -674,9 +1509,9 @@ impl Db {
         for emotion in self.get_emotions().await? {
             let name = emotion.name;
 
-            let list: Vec<(String, String, i64)> = sqlx::query_as(
+            let list: Vec<(String, String, String, i64)> = sqlx::query_as(
                 "
-                SELECT t.name, t.extension, et.rank
+                SELECT t.path, t.name, t.extension, et.rank
                 FROM tracks AS t
                 JOIN emotion_tracks AS et ON et.track_hash = t.hash
                 WHERE et.emotion_name = $1                
@@ -692,7 +1527,8 @@ impl Db {
 
             let data = json!({
                 "name": name,
-                "list": list.into_iter().map(|(name, extension, rank)| json!({
+                "list": list.into_iter().map(|(path, name, extension, rank)| json!({
+                    "path": relative(&path),
                     "file_name": format!("{name}.{extension}"),
                     "rank": rank
                 })).collect::<Vec<_>>(),
@@ -705,6 +1541,7 @@ impl Db {
         let list: Vec<TrackExtendedRow> = sqlx::query_as(
             "
             SELECT
+                t.path AS path,
                 t.name AS name,
                 t.extension AS extension,
                 l.plain AS plain_lyrics,
@@ -723,6 +1560,7 @@ impl Db {
         if !list.is_empty() {
             let data = list.into_iter().map(|item| {
                 json!({
+                    "path": relative(&item.path),
                     "file_name": format!("{}.{}", item.name, item.extension),
                     "rules": item.rules.filter(|x| !x.trim().is_empty()),
                     "lyrics": (item.plain_lyrics.is_some() || item.synced_lyrics.is_some()).then(|| {
@@ -762,10 +1600,375 @@ impl Db {
             .execute(&self.pool)
             .await?;
 
+        self.migrate().await?;
+
+        Ok(())
+    }
+
+    // init.sql only creates missing tables, so columns added after a database
+    // was first created are patched in here
+    async fn migrate(&self) -> Result<()> {
+        const COLUMNS: &[(&str, &str, &str)] = &[
+            ("tracks", "size", "INTEGER NOT NULL DEFAULT 0"),
+            ("tracks", "mtime", "INTEGER NOT NULL DEFAULT 0"),
+            ("tracks", "track_gain", "REAL"),
+            ("tracks", "track_peak", "REAL"),
+            ("tracks", "album_gain", "REAL"),
+            ("tracks", "album_peak", "REAL"),
+            ("tracks", "loudness", "REAL"),
+            ("tracks", "true_peak", "REAL"),
+            ("tracks", "analyzed", "INTEGER NOT NULL DEFAULT 0"),
+            ("tracks", "track_number", "INTEGER"),
+            ("tracks", "sample_rate", "INTEGER"),
+            ("tracks", "bit_depth", "INTEGER"),
+            ("tracks", "channels", "INTEGER"),
+            ("tracks", "codec", "TEXT"),
+            ("tracks", "bitrate", "INTEGER"),
+            ("tracks", "track_total", "INTEGER"),
+            ("tracks", "disc_number", "INTEGER"),
+            ("tracks", "disc_total", "INTEGER"),
+            ("tracks", "composer", "TEXT"),
+            ("tracks", "conductor", "TEXT"),
+            ("tracks", "bpm", "REAL"),
+            ("tracks", "comment", "TEXT"),
+            ("tracks", "label", "TEXT"),
+            ("tracks", "isrc", "TEXT"),
+            ("tracks", "musicbrainz_track_id", "TEXT"),
+            ("tracks", "musicbrainz_album_id", "TEXT"),
+            ("tracks", "musicbrainz_artist_id", "TEXT"),
+            ("tracks", "musicbrainz_album_artist_id", "TEXT"),
+            ("tracks", "musicbrainz_release_group_id", "TEXT"),
+            ("tracks", "sort_title", "TEXT"),
+            ("tracks", "sort_artist", "TEXT"),
+            ("tracks", "sort_album", "TEXT"),
+            ("tracks", "sort_album_artist", "TEXT"),
+            ("tracks", "sort_composer", "TEXT"),
+            ("tracks", "compilation", "INTEGER NOT NULL DEFAULT 0"),
+            ("dirs", "excludes", "TEXT NOT NULL DEFAULT '[]'"),
+            ("dirs", "follow_links", "INTEGER NOT NULL DEFAULT 0"),
+            ("dirs", "max_depth", "INTEGER"),
+            ("dirs", "skip_hidden", "INTEGER NOT NULL DEFAULT 0"),
+        ];
+
+        for (table, column, definition) in COLUMNS {
+            let exists: bool = sqlx::query_scalar(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info($1) WHERE name = $2",
+            )
+            .bind(table)
+            .bind(column)
+            .fetch_one(&self.pool)
+            .await?;
+
+            if !exists {
+                sqlx::query(&format!(
+                    "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+                ))
+                .execute(&self.pool)
+                .await?;
+            }
+        }
+
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&self.pool)
+            .await?;
+
+        // databases from before versioning hash tracks by file name and keep durations in whole
+        // seconds, clearing mtime gets the next scan to probe every file again for everything
+        // read from them since, as unchanged files are skipped otherwise
+        if version < 1 {
+            let mut tx = self.pool.begin().await?;
+
+            rehash_tracks(&mut tx).await?;
+
+            sqlx::query("UPDATE tracks SET duration = duration * 1000, mtime = 0")
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("PRAGMA user_version = 1")
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await?;
+        }
+
         Ok(())
     }
 }
 
+// SQLite caps the number of bound parameters in a single statement (32766 by default),
+// so bulk statements are split into batches that stay under it with a little headroom
+// for binds outside the batch
+const MAX_BINDS: usize = 32000;
+
+fn batches<T>(items: &[T], binds_per_item: usize) -> Chunks<'_, T> {
+    items.chunks(MAX_BINDS / binds_per_item)
+}
+
+const TRACK_COLUMNS: &str = "hash, path, name, extension, duration, sample_rate, bit_depth, channels, codec, bitrate, cover, title, artist, album, album_artist, date, genre, track_number, track_total, disc_number, disc_total, composer, conductor, bpm, comment, label, isrc, musicbrainz_track_id, musicbrainz_album_id, musicbrainz_artist_id, musicbrainz_album_artist_id, musicbrainz_release_group_id, sort_title, sort_artist, sort_album, sort_album_artist, sort_composer, compilation, size, mtime, track_gain, track_peak, album_gain, album_peak";
+
+async fn upsert_tracks(conn: &mut SqliteConnection, tracks: &[Track]) -> Result<()> {
+    for batch in batches(tracks, TRACK_COLUMNS.split(',').count()) {
+        let mut qb = QueryBuilder::new(format!("INSERT INTO tracks ({TRACK_COLUMNS}) "));
+
+        qb.push_values(batch, |mut b, track| {
+            b.push_bind(&track.hash)
+                .push_bind(track.path.to_string_lossy().to_string())
+                .push_bind(&track.name)
+                .push_bind(&track.extension)
+                .push_bind(track.duration as i64)
+                .push_bind(track.sample_rate)
+                .push_bind(track.bit_depth)
+                .push_bind(track.channels)
+                .push_bind(&track.codec)
+                .push_bind(track.bitrate)
+                .push_bind(
+                    track
+                        .cover
+                        .as_ref()
+                        .map(|p| p.to_string_lossy().to_string()),
+                )
+                .push_bind(&track.title)
+                .push_bind(&track.artist)
+                .push_bind(&track.album)
+                .push_bind(&track.album_artist)
+                .push_bind(&track.date)
+                .push_bind(&track.genre)
+                .push_bind(track.track_number)
+                .push_bind(track.track_total)
+                .push_bind(track.disc_number)
+                .push_bind(track.disc_total)
+                .push_bind(&track.composer)
+                .push_bind(&track.conductor)
+                .push_bind(track.bpm)
+                .push_bind(&track.comment)
+                .push_bind(&track.label)
+                .push_bind(&track.isrc)
+                .push_bind(&track.musicbrainz_track_id)
+                .push_bind(&track.musicbrainz_album_id)
+                .push_bind(&track.musicbrainz_artist_id)
+                .push_bind(&track.musicbrainz_album_artist_id)
+                .push_bind(&track.musicbrainz_release_group_id)
+                .push_bind(&track.sort_title)
+                .push_bind(&track.sort_artist)
+                .push_bind(&track.sort_album)
+                .push_bind(&track.sort_album_artist)
+                .push_bind(&track.sort_composer)
+                .push_bind(track.compilation)
+                .push_bind(track.size as i64)
+                .push_bind(track.mtime as i64)
+                .push_bind(track.track_gain)
+                .push_bind(track.track_peak)
+                .push_bind(track.album_gain)
+                .push_bind(track.album_peak);
+        });
+
+        qb.push(
+            "
+            ON CONFLICT(hash) DO UPDATE SET
+                path = excluded.path,
+                name = excluded.name,
+                extension = excluded.extension,
+                duration = excluded.duration,
+                sample_rate = excluded.sample_rate,
+                bit_depth = excluded.bit_depth,
+                channels = excluded.channels,
+                codec = excluded.codec,
+                bitrate = excluded.bitrate,
+                cover = excluded.cover,
+                title = excluded.title,
+                artist = excluded.artist,
+                album = excluded.album,
+                album_artist = excluded.album_artist,
+                date = excluded.date,
+                genre = excluded.genre,
+                track_number = excluded.track_number,
+                track_total = excluded.track_total,
+                disc_number = excluded.disc_number,
+                disc_total = excluded.disc_total,
+                composer = excluded.composer,
+                conductor = excluded.conductor,
+                bpm = excluded.bpm,
+                comment = excluded.comment,
+                label = excluded.label,
+                isrc = excluded.isrc,
+                musicbrainz_track_id = excluded.musicbrainz_track_id,
+                musicbrainz_album_id = excluded.musicbrainz_album_id,
+                musicbrainz_artist_id = excluded.musicbrainz_artist_id,
+                musicbrainz_album_artist_id = excluded.musicbrainz_album_artist_id,
+                musicbrainz_release_group_id = excluded.musicbrainz_release_group_id,
+                sort_title = excluded.sort_title,
+                sort_artist = excluded.sort_artist,
+                sort_album = excluded.sort_album,
+                sort_album_artist = excluded.sort_album_artist,
+                sort_composer = excluded.sort_composer,
+                compilation = excluded.compilation,
+                size = excluded.size,
+                mtime = excluded.mtime,
+                track_gain = excluded.track_gain,
+                track_peak = excluded.track_peak,
+                album_gain = excluded.album_gain,
+                album_peak = excluded.album_peak,
+                loudness = NULL,
+                true_peak = NULL,
+                analyzed = 0
+            WHERE tracks.path = excluded.path
+            ",
+        );
+
+        qb.build().execute(&mut *conn).await?;
+    }
+
+    Ok(())
+}
+
+// tracks used to be hashed by file name, this carries everything keyed by the old hashes over
+async fn rehash_tracks(conn: &mut SqliteConnection) -> Result<()> {
+    let rows: Vec<(String, String)> = sqlx::query_as("SELECT hash, path FROM tracks")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    let changes: Vec<(String, String)> = rows
+        .into_iter()
+        .filter_map(|(old, path)| {
+            let new = tracks::hash_path(Path::new(&path));
+            (new != old).then_some((old, new))
+        })
+        .collect();
+
+    rekey_tracks(conn, &changes).await
+}
+
+// moves a track and everything keyed by its hash (playlists, emotions, lyrics, rules) over to a new hash
+async fn rekey_tracks(conn: &mut SqliteConnection, changes: &[(String, String)]) -> Result<()> {
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query("CREATE TEMP TABLE rehash (old TEXT PRIMARY KEY, new TEXT NOT NULL)")
+        .execute(&mut *conn)
+        .await?;
+
+    for batch in batches(changes, 2) {
+        QueryBuilder::new("INSERT OR IGNORE INTO rehash (old, new) ")
+            .push_values(batch, |mut b, (old, new)| {
+                b.push_bind(old).push_bind(new);
+            })
+            .build()
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    // every old key is parked under a prefix no hash has before moving to its new one, so one
+    // track taking over another's old hash along the way can't clash, whatever is still parked
+    // afterwards lost to a track that already had the new hash and goes, along with anything
+    // else that was its own rather than moving over to the track it clashed with
+    sqlx::query(
+        "
+        UPDATE tracks SET hash = '~' || hash WHERE hash IN (SELECT old FROM rehash);
+        UPDATE OR IGNORE tracks SET hash = r.new FROM rehash AS r WHERE tracks.hash = '~' || r.old;
+        CREATE TEMP TABLE rehash_lost AS SELECT old FROM rehash WHERE '~' || old IN (SELECT hash FROM tracks);
+        DELETE FROM tracks WHERE hash IN (SELECT '~' || old FROM rehash);
+
+        DELETE FROM playlist_tracks WHERE track_hash IN (SELECT old FROM rehash_lost);
+        UPDATE playlist_tracks SET track_hash = '~' || track_hash WHERE track_hash IN (SELECT old FROM rehash);
+        UPDATE OR IGNORE playlist_tracks SET track_hash = r.new FROM rehash AS r WHERE track_hash = '~' || r.old;
+        DELETE FROM playlist_tracks WHERE track_hash IN (SELECT '~' || old FROM rehash);
+
+        DELETE FROM emotion_tracks WHERE track_hash IN (SELECT old FROM rehash_lost);
+        UPDATE emotion_tracks SET track_hash = '~' || track_hash WHERE track_hash IN (SELECT old FROM rehash);
+        UPDATE OR IGNORE emotion_tracks SET track_hash = r.new FROM rehash AS r WHERE track_hash = '~' || r.old;
+        DELETE FROM emotion_tracks WHERE track_hash IN (SELECT '~' || old FROM rehash);
+
+        DELETE FROM track_equalizers WHERE track_hash IN (SELECT old FROM rehash_lost);
+        UPDATE track_equalizers SET track_hash = '~' || track_hash WHERE track_hash IN (SELECT old FROM rehash);
+        UPDATE OR IGNORE track_equalizers SET track_hash = r.new FROM rehash AS r WHERE track_hash = '~' || r.old;
+        DELETE FROM track_equalizers WHERE track_hash IN (SELECT '~' || old FROM rehash);
+
+        DELETE FROM lyrics WHERE track_hash IN (SELECT old FROM rehash_lost);
+        UPDATE lyrics SET track_hash = r.new FROM rehash AS r WHERE track_hash = r.old;
+
+        DELETE FROM ruleset WHERE track_hash IN (SELECT old FROM rehash_lost);
+        UPDATE ruleset SET track_hash = r.new FROM rehash AS r WHERE track_hash = r.old;
+
+        DROP TABLE rehash_lost;
+        DROP TABLE rehash;
+        ",
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+// the track at `path`, or every track under it when it's a folder
+async fn under_path(conn: &mut SqliteConnection, path: &Path) -> Result<Vec<(String, String)>> {
+    let path = path.to_string_lossy();
+    let prefix = format!("{path}{MAIN_SEPARATOR}");
+
+    let rows: Vec<(String, String)> =
+        sqlx::query_as("SELECT hash, path FROM tracks WHERE path = $1 OR substr(path, 1, $2) = $3")
+            .bind(path.as_ref())
+            .bind(prefix.chars().count() as i64)
+            .bind(&prefix)
+            .fetch_all(&mut *conn)
+            .await?;
+
+    Ok(rows)
+}
+
+// whether saving these would leave a row with a different cover than it has now
+async fn covers_changed(conn: &mut SqliteConnection, tracks: &[Track]) -> Result<bool> {
+    for batch in batches(tracks, 1) {
+        let mut qb: QueryBuilder<Sqlite> =
+            QueryBuilder::new("SELECT hash, cover FROM tracks WHERE hash IN (");
+        let mut separated = qb.separated(", ");
+
+        for track in batch {
+            separated.push_bind(&track.hash);
+        }
+
+        qb.push(")");
+
+        let rows: HashMap<String, Option<String>> = qb
+            .build_query_as::<(String, Option<String>)>()
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+
+        let changed = batch.iter().any(|track| {
+            rows.get(&track.hash)
+                .is_some_and(|cover| cover.as_deref().map(Path::new) != track.cover.as_deref())
+        });
+
+        if changed {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+async fn replace_scan_errors(conn: &mut SqliteConnection, errors: &[ScanError]) -> Result<()> {
+    sqlx::query("DELETE FROM scan_errors")
+        .execute(&mut *conn)
+        .await?;
+
+    for batch in batches(errors, 3) {
+        QueryBuilder::new("INSERT OR REPLACE INTO scan_errors (path, kind, message) ")
+            .push_values(batch, |mut b, error| {
+                b.push_bind(&error.path)
+                    .push_bind(error.kind)
+                    .push_bind(&error.message);
+            })
+            .build()
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
 #[derive(sqlx::FromRow)]
 pub struct TrackRow {
     pub hash: String,
@@ -773,6 +1976,11 @@ pub struct TrackRow {
     pub name: String,
     pub extension: String,
     pub duration: i64,
+    pub sample_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+    pub codec: Option<String>,
+    pub bitrate: Option<u32>,
     pub cover: Option<String>,
     pub title: Option<String>,
     pub artist: Option<String>,
@@ -780,6 +1988,33 @@ pub struct TrackRow {
     pub album_artist: Option<String>,
     pub date: Option<String>,
     pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub composer: Option<String>,
+    pub conductor: Option<String>,
+    pub bpm: Option<f32>,
+    pub comment: Option<String>,
+    pub label: Option<String>,
+    pub isrc: Option<String>,
+    pub musicbrainz_track_id: Option<String>,
+    pub musicbrainz_album_id: Option<String>,
+    pub musicbrainz_artist_id: Option<String>,
+    pub musicbrainz_album_artist_id: Option<String>,
+    pub musicbrainz_release_group_id: Option<String>,
+    pub sort_title: Option<String>,
+    pub sort_artist: Option<String>,
+    pub sort_album: Option<String>,
+    pub sort_album_artist: Option<String>,
+    pub sort_composer: Option<String>,
+    pub compilation: bool,
+    pub size: i64,
+    pub mtime: i64,
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
     pub rules: Option<String>,
     #[sqlx(default)]
     pub position: Option<i64>,
@@ -787,8 +2022,57 @@ pub struct TrackRow {
     pub rank: Option<i64>,
 }
 
+#[derive(sqlx::FromRow)]
+pub struct SessionRow {
+    pub current: i64,
+    pub position: i64,
+    pub volume: f32,
+    pub repeat: Option<Repeat>,
+    pub shuffle_seed: Option<u32>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct PlaybackInfoRow {
+    pub path: String,
+    pub duration: i64,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+    pub loudness: Option<f32>,
+    pub true_peak: Option<f32>,
+    pub equalizer: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+pub struct EqualizerPresetRow {
+    pub name: String,
+    pub bands: String,
+}
+
+impl From<EqualizerPresetRow> for EqualizerPreset {
+    fn from(row: EqualizerPresetRow) -> Self {
+        Self {
+            name: row.name,
+            bands: serde_json::from_str(&row.bands).unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct DirRow {
+    pub path: String,
+    pub excludes: String,
+    pub follow_links: bool,
+    pub max_depth: Option<i64>,
+    pub skip_hidden: bool,
+}
+
 #[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
 pub struct TrackExtendedRow {
+    pub path: String,
     pub name: String,
     pub extension: String,
     pub plain_lyrics: Option<String>,
@@ -796,6 +2080,13 @@ pub struct TrackExtendedRow {
     pub rules: Option<String>,
 }
 
+// presets set for a track itself and for its album, the track's wins
+#[derive(sqlx::FromRow, Debug, Default, Serialize)]
+pub struct EqualizerAssignment {
+    pub track: Option<String>,
+    pub album: Option<String>,
+}
+
 #[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
 pub struct Emotion {
     pub name: String,
@@ -803,8 +2094,384 @@ pub struct Emotion {
     pub icon: String,
 }
 
+// a batch of tag changes that can still be undone
+#[derive(sqlx::FromRow, Debug, Serialize)]
+pub struct TagEdit {
+    pub id: i64,
+    pub summary: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ScanSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub errors: Vec<ScanError>,
+}
+
+#[derive(Debug, Default)]
+pub struct LibraryChanges {
+    pub tracks: Vec<Track>,
+    pub removed: Vec<PathBuf>,
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl LibraryChanges {
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty() && self.removed.is_empty() && self.renamed.is_empty()
+    }
+}
+
 #[derive(Deserialize)]
 pub struct GetTracksFilters {
     pub album: Option<String>,
     pub artist: Option<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    // a fresh database per test, gone with its only connection
+    async fn memory_db() -> Result<Db> {
+        let options = SqliteConnectOptions::from_str("sqlite::memory:")?;
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(options)
+            .await?;
+
+        let db = Db {
+            pool,
+            covers_path: std::env::temp_dir().join("meowsic-test-covers"),
+        };
+
+        db.init().await?;
+
+        Ok(db)
+    }
+
+    fn track(i: usize) -> Track {
+        Track {
+            hash: format!("hash-{i}"),
+            path: PathBuf::from(format!("/music/{i:05}.flac")),
+            name: format!("{i:05}.flac"),
+            extension: "flac".into(),
+            ..Track::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn upserts_more_tracks_than_one_statement_can_bind() -> Result<()> {
+        let db = memory_db().await?;
+        let tracks: Vec<Track> = (0..40_000).map(track).collect();
+
+        let mut tx = db.pool.begin().await?;
+        upsert_tracks(&mut tx, &tracks).await?;
+        tx.commit().await?;
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM tracks")
+            .fetch_one(&db.pool)
+            .await?;
+
+        assert_eq!(count, 40_000);
+
+        let last: String = sqlx::query_scalar("SELECT path FROM tracks WHERE hash = 'hash-39999'")
+            .fetch_one(&db.pool)
+            .await?;
+
+        assert_eq!(last, "/music/39999.flac");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn adds_and_removes_more_playlist_tracks_than_one_statement_can_bind() -> Result<()> {
+        let db = memory_db().await?;
+        let hashes: Vec<String> = (0..40_000).map(|i| format!("hash-{i}")).collect();
+
+        db.add_playlist("Mix").await?;
+        db.add_playlist_tracks("Mix", &hashes).await?;
+
+        let (count, last): (i64, i64) = sqlx::query_as(
+            "SELECT COUNT(*), MAX(position) FROM playlist_tracks WHERE playlist_name = 'Mix'",
+        )
+        .fetch_one(&db.pool)
+        .await?;
+
+        assert_eq!((count, last), (40_000, 39_999));
+
+        // more than one statement's worth, the rest move up to close the gap
+        db.remove_playlist_tracks("Mix", Some(&hashes[..35_000]))
+            .await?;
+
+        let left: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT track_hash, position FROM playlist_tracks WHERE playlist_name = 'Mix' ORDER BY position",
+        )
+        .fetch_all(&db.pool)
+        .await?;
+
+        let expected: Vec<(String, i64)> = (0..5_000)
+            .map(|i| (format!("hash-{}", i + 35_000), i))
+            .collect();
+
+        assert_eq!(left, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn restores_more_tracks_than_one_statement_can_bind() -> Result<()> {
+        let tracks: Vec<Track> = (0..40_000).map(track).collect();
+        let dir = std::env::temp_dir().join("meowsic-test-backup");
+        fs::create_dir_all(&dir)?;
+
+        let from = memory_db().await?;
+        from.set_dirs(&["/music"]).await?;
+        from.add_playlist("Mix").await?;
+
+        let mut tx = from.pool.begin().await?;
+        upsert_tracks(&mut tx, &tracks).await?;
+
+        sqlx::query(
+            "
+            INSERT INTO playlist_tracks (playlist_name, track_hash, position)
+            SELECT 'Mix', hash, rowid FROM tracks;
+            INSERT INTO emotion_tracks (emotion_name, track_hash, rank)
+            SELECT 'Happy', hash, rowid FROM tracks;
+            INSERT INTO lyrics (track_hash, plain, synced) SELECT hash, 'plain', '' FROM tracks;
+            INSERT INTO ruleset (track_hash, rules) SELECT hash, 'rules' FROM tracks;
+            ",
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let backup = from.backup(&dir).await?;
+
+        let to = memory_db().await?;
+        to.set_dirs(&["/music"]).await?;
+
+        let mut tx = to.pool.begin().await?;
+        upsert_tracks(&mut tx, &tracks).await?;
+        tx.commit().await?;
+
+        to.restore(&backup).await?;
+
+        let counts: (i64, i64, i64, i64) = sqlx::query_as(
+            "
+            SELECT
+                (SELECT COUNT(*) FROM playlist_tracks WHERE playlist_name = 'Mix (Restored)'),
+                (SELECT COUNT(*) FROM emotion_tracks WHERE emotion_name = 'Happy'),
+                (SELECT COUNT(*) FROM lyrics),
+                (SELECT COUNT(*) FROM ruleset)
+            ",
+        )
+        .fetch_one(&to.pool)
+        .await?;
+
+        assert_eq!(counts, (40_000, 40_000, 40_000, 40_000));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn keeps_the_first_file_when_two_paths_share_a_hash() -> Result<()> {
+        let db = memory_db().await?;
+        let other = Track {
+            path: PathBuf::from("/other/00001.flac"),
+            ..track(1)
+        };
+
+        let mut tx = db.pool.begin().await?;
+        upsert_tracks(&mut tx, &[track(1)]).await?;
+        upsert_tracks(&mut tx, &[other]).await?;
+        tx.commit().await?;
+
+        let paths: Vec<String> = sqlx::query_scalar("SELECT path FROM tracks")
+            .fetch_all(&db.pool)
+            .await?;
+
+        assert_eq!(paths, ["/music/00001.flac"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rekeys_chains_and_clashes_without_leaving_old_rows() -> Result<()> {
+        let db = memory_db().await?;
+        let tracks: Vec<Track> = (1..=4).map(track).collect();
+
+        let mut tx = db.pool.begin().await?;
+        upsert_tracks(&mut tx, &tracks).await?;
+
+        sqlx::query("INSERT INTO playlists (name) VALUES ('Mix')")
+            .execute(&mut *tx)
+            .await?;
+
+        for (position, track) in tracks.iter().enumerate() {
+            sqlx::query("INSERT INTO playlist_tracks (playlist_name, track_hash, position) VALUES ('Mix', $1, $2)")
+                .bind(&track.hash)
+                .bind(position as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // 4 is on neither of these, nothing of 3 should end up on it
+        sqlx::query("INSERT INTO playlists (name) VALUES ('Other')")
+            .execute(&mut *tx)
+            .await?;
+
+        for (position, hash) in ["hash-1", "hash-3"].iter().enumerate() {
+            sqlx::query("INSERT INTO playlist_tracks (playlist_name, track_hash, position) VALUES ('Other', $1, $2)")
+                .bind(hash)
+                .bind(position as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for i in [1, 3] {
+            sqlx::query("INSERT INTO lyrics (track_hash, plain, synced) VALUES ($1, $2, '')")
+                .bind(format!("hash-{i}"))
+                .bind(format!("lyrics of {i}"))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        for i in [2, 3] {
+            sqlx::query("INSERT INTO ruleset (track_hash, rules) VALUES ($1, $2)")
+                .bind(format!("hash-{i}"))
+                .bind(format!("rules of {i}"))
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        // 1 takes over the hash 2 is leaving, 3 runs into 4 which stays where it is
+        let changes = [
+            ("hash-1", "hash-2"),
+            ("hash-2", "hash-5"),
+            ("hash-3", "hash-4"),
+        ]
+        .map(|(old, new)| (old.to_string(), new.to_string()));
+
+        rekey_tracks(&mut tx, &changes).await?;
+        tx.commit().await?;
+
+        let hashes: Vec<String> = sqlx::query_scalar("SELECT hash FROM tracks ORDER BY hash")
+            .fetch_all(&db.pool)
+            .await?;
+
+        let linked: Vec<(String, String)> = sqlx::query_as(
+            "SELECT playlist_name, track_hash FROM playlist_tracks ORDER BY playlist_name, track_hash",
+        )
+        .fetch_all(&db.pool)
+        .await?;
+
+        let lyrics: Vec<(String, String)> =
+            sqlx::query_as("SELECT track_hash, plain FROM lyrics ORDER BY track_hash")
+                .fetch_all(&db.pool)
+                .await?;
+
+        let rules: Vec<(String, String)> =
+            sqlx::query_as("SELECT track_hash, rules FROM ruleset ORDER BY track_hash")
+                .fetch_all(&db.pool)
+                .await?;
+
+        let pairs = |x: &[(&str, &str)]| -> Vec<(String, String)> {
+            x.iter()
+                .map(|(a, b)| (a.to_string(), b.to_string()))
+                .collect()
+        };
+
+        assert_eq!(hashes, ["hash-2", "hash-4", "hash-5"]);
+        assert_eq!(
+            linked,
+            pairs(&[
+                ("Mix", "hash-2"),
+                ("Mix", "hash-4"),
+                ("Mix", "hash-5"),
+                ("Other", "hash-2"),
+            ])
+        );
+        assert_eq!(lyrics, pairs(&[("hash-2", "lyrics of 1")]));
+        assert_eq!(rules, pairs(&[("hash-5", "rules of 2")]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn migrates_databases_from_before_versioning_once() -> Result<()> {
+        let db = memory_db().await?;
+        let old = crate::utils::hash(b"a.flac");
+
+        sqlx::query("INSERT INTO tracks (hash, path, name, extension, duration, mtime) VALUES ($1, '/music/a.flac', 'a', 'flac', 3, 99)")
+            .bind(&old)
+            .execute(&db.pool)
+            .await?;
+
+        sqlx::query("INSERT INTO lyrics (track_hash, plain, synced) VALUES ($1, 'la', '')")
+            .bind(&old)
+            .execute(&db.pool)
+            .await?;
+
+        sqlx::query("PRAGMA user_version = 0")
+            .execute(&db.pool)
+            .await?;
+
+        db.migrate().await?;
+        db.migrate().await?;
+
+        let new = tracks::hash_path(Path::new("/music/a.flac"));
+        let row: (String, i64, i64) = sqlx::query_as("SELECT hash, duration, mtime FROM tracks")
+            .fetch_one(&db.pool)
+            .await?;
+
+        let lyrics: String = sqlx::query_scalar("SELECT track_hash FROM lyrics")
+            .fetch_one(&db.pool)
+            .await?;
+
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+            .fetch_one(&db.pool)
+            .await?;
+
+        assert_eq!(row, (new.clone(), 3000, 0));
+        assert_eq!(lyrics, new);
+        assert_eq!(version, 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn leaves_failed_measurements_alone_until_the_file_changes() -> Result<()> {
+        let db = memory_db().await?;
+        let tracks = [track(0), track(1)];
+
+        let mut tx = db.pool.begin().await?;
+        upsert_tracks(&mut tx, &tracks).await?;
+        tx.commit().await?;
+
+        let measured = Loudness {
+            integrated: -14.0,
+            true_peak: 0.9,
+        };
+
+        db.set_loudness(&[("hash-0".into(), Some(measured)), ("hash-1".into(), None)])
+            .await?;
+
+        assert!(db.get_unanalyzed_tracks(10).await?.is_empty());
+
+        // saved again as a scan or the watcher would after the file changed
+        let mut tx = db.pool.begin().await?;
+        upsert_tracks(&mut tx, &tracks[1..]).await?;
+        tx.commit().await?;
+
+        let pending = db.get_unanalyzed_tracks(10).await?;
+        assert_eq!(
+            pending,
+            [("hash-1".to_string(), PathBuf::from("/music/00001.flac"))]
+        );
+
+        Ok(())
+    }
+}
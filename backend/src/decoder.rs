@@ -0,0 +1,316 @@
+use anyhow::{Context, Result};
+use rodio::Source;
+use rodio::source::SeekError;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::{Time, TimeBase};
+use symphonia::default::{get_codecs, get_probe};
+
+// where a seek actually landed, which can be short of where it was asked to go (past the
+// end, a stream that ends early), shared since the decoder itself disappears into the sink
+#[derive(Debug, Default)]
+pub struct SeekPosition(AtomicU64);
+
+impl SeekPosition {
+    pub fn get(&self) -> Duration {
+        Duration::from_micros(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: Duration) {
+        self.0.store(value.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+// plays a file through symphonia, like rodio's decoder does, but keeps track of the
+// timestamps of what it decodes so seeking can say where it ended up
+pub struct TrackDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: TimeBase,
+    duration: Option<Duration>,
+    spec: SignalSpec,
+    buffer: Option<SampleBuffer<f32>>,
+    // interleaved samples of the buffer already handed out
+    offset: usize,
+    seeked: Arc<SeekPosition>,
+}
+
+impl TrackDecoder {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let mss = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut hint = Hint::new();
+
+        if let Some(extension) = path.extension().and_then(|x| x.to_str()) {
+            hint.with_extension(extension);
+        }
+
+        // encoder delay and padding are trimmed, so albums meant to be gapless play that way
+        let options = FormatOptions {
+            enable_gapless: true,
+            ..FormatOptions::default()
+        };
+
+        let probed = get_probe().format(&hint, mss, &options, &MetadataOptions::default())?;
+
+        let track = probed
+            .format
+            .tracks()
+            .iter()
+            .find(|x| x.codec_params.codec != CODEC_TYPE_NULL)
+            .context("no audio track found")?;
+
+        let params = &track.codec_params;
+        let time_base = params
+            .time_base
+            .or_else(|| params.sample_rate.map(|x| TimeBase::new(1, x)))
+            .context("unknown time base")?;
+
+        let mut decoder = Self {
+            track_id: track.id,
+            time_base,
+            duration: params.n_frames.map(|x| to_duration(time_base.calc_time(x))),
+            decoder: get_codecs().make(params, &DecoderOptions::default())?,
+            spec: SignalSpec::new(params.sample_rate.unwrap_or(44100), Default::default()),
+            format: probed.format,
+            buffer: None,
+            offset: 0,
+            seeked: Arc::default(),
+        };
+
+        // the channel layout isn't always known until the first frame is decoded
+        decoder.refill().context("no audio decoded")?;
+
+        Ok(decoder)
+    }
+
+    pub fn seeked(&self) -> Arc<SeekPosition> {
+        self.seeked.clone()
+    }
+
+    // decodes the next packet with anything in it, returning its timestamp,
+    // `None` once the stream runs out
+    fn refill(&mut self) -> Option<u64> {
+        self.offset = 0;
+
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => {
+                    self.buffer = None;
+                    return None;
+                }
+            };
+
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) if decoded.frames() > 0 => decoded,
+                // a corrupt frame here and there shouldn't end the whole track
+                Ok(_) | Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(_) => {
+                    self.buffer = None;
+                    return None;
+                }
+            };
+
+            self.spec = *decoded.spec();
+
+            // the sample buffer counts samples across all channels, the decoded one frames
+            let samples = decoded.capacity() * self.spec.channels.count();
+
+            if self.buffer.as_ref().is_none_or(|x| x.capacity() < samples) {
+                self.buffer = Some(SampleBuffer::new(decoded.capacity() as u64, self.spec));
+            }
+
+            let buffer = self.buffer.as_mut()?;
+            buffer.copy_interleaved_ref(decoded);
+
+            return Some(packet.ts());
+        }
+    }
+
+    fn frames(&self) -> u64 {
+        let channels = self.spec.channels.count().max(1);
+        self.buffer
+            .as_ref()
+            .map_or(0, |x| (x.len() / channels) as u64)
+    }
+}
+
+impl Iterator for TrackDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = *self.buffer.as_ref()?.samples().get(self.offset)?;
+        self.offset += 1;
+
+        // decoding ahead keeps `current_frame_len` honest about when the layout may change
+        if self.offset >= self.buffer.as_ref().map_or(0, |x| x.len()) {
+            self.refill();
+        }
+
+        Some(sample)
+    }
+}
+
+impl Source for TrackDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.buffer.as_ref().map_or(0, |x| x.len()) - self.offset)
+    }
+
+    fn channels(&self) -> u16 {
+        self.spec.channels.count() as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.spec.rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // some formats refuse to seek right onto the very end
+        let pos = match self.duration {
+            Some(duration) => pos.min(duration.saturating_sub(Duration::from_millis(1))),
+            None => pos,
+        };
+
+        let to = SeekTo::Time {
+            time: Time::from(pos.as_secs_f64()),
+            track_id: Some(self.track_id),
+        };
+
+        let seeked = self
+            .format
+            .seek(SeekMode::Accurate, to)
+            .map_err(|err| SeekError::Other(Box::new(err)))?;
+
+        self.decoder.reset();
+
+        // the demuxer stops at a packet at or before the frame asked for, the
+        // rest of the way is decoded and skipped
+        let mut landed = seeked.actual_ts;
+
+        while let Some(ts) = self.refill() {
+            let frames = self.frames();
+
+            if ts + frames > seeked.required_ts {
+                let skip = seeked.required_ts.saturating_sub(ts);
+
+                self.offset = skip as usize * self.spec.channels.count();
+                landed = ts + skip;
+                break;
+            }
+
+            landed = ts + frames;
+        }
+
+        self.seeked
+            .set(to_duration(self.time_base.calc_time(landed)));
+
+        Ok(())
+    }
+}
+
+fn to_duration(time: Time) -> Duration {
+    Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    const RATE: u32 = 8000;
+
+    // a second of mono PCM where every sample holds its own index
+    fn write_wav(name: &str) -> PathBuf {
+        let samples: Vec<u8> = (0..RATE as i16).flat_map(|x| x.to_le_bytes()).collect();
+
+        let mut data = Vec::new();
+        data.extend(b"RIFF");
+        data.extend((36 + samples.len() as u32).to_le_bytes());
+        data.extend(b"WAVEfmt ");
+        data.extend(16u32.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        data.extend(RATE.to_le_bytes());
+        data.extend((RATE * 2).to_le_bytes());
+        data.extend(2u16.to_le_bytes());
+        data.extend(16u16.to_le_bytes());
+        data.extend(b"data");
+        data.extend((samples.len() as u32).to_le_bytes());
+        data.extend(samples);
+
+        let path = std::env::temp_dir().join(format!("meowsic-{name}.wav"));
+        std::fs::write(&path, data).unwrap();
+
+        path
+    }
+
+    fn index(sample: f32) -> i32 {
+        (sample * 32768.0).round() as i32
+    }
+
+    #[test]
+    fn seeks_to_the_exact_frame() -> Result<()> {
+        let mut decoder = TrackDecoder::open(write_wav("seek"))?;
+
+        assert_eq!(decoder.total_duration(), Some(Duration::from_secs(1)));
+
+        decoder
+            .try_seek(Duration::from_millis(500))
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+
+        assert_eq!(decoder.seeked().get(), Duration::from_millis(500));
+        assert_eq!(decoder.next().map(index), Some(4000));
+
+        // the frame isn't rounded to whole milliseconds
+        decoder
+            .try_seek(Duration::from_micros(250_125))
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+
+        assert_eq!(decoder.seeked().get(), Duration::from_micros(250_125));
+        assert_eq!(decoder.next().map(index), Some(2001));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lands_at_the_end_when_asked_to_go_past_it() -> Result<()> {
+        let mut decoder = TrackDecoder::open(write_wav("past-end"))?;
+
+        decoder
+            .try_seek(Duration::from_secs(5))
+            .map_err(|err| anyhow::anyhow!("{err}"))?;
+
+        let landed = decoder.seeked().get();
+        let remaining = decoder.count();
+
+        assert!(landed <= Duration::from_secs(1));
+        assert!(landed >= Duration::from_millis(998), "{landed:?}");
+        assert_eq!(
+            remaining as u128,
+            (1_000_000 - landed.as_micros()) * 8 / 1000
+        );
+
+        Ok(())
+    }
+}
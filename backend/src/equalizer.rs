@@ -0,0 +1,301 @@
+use parking_lot::Mutex;
+use rodio::Source;
+use rodio::source::SeekError;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// long enough to hide the switch, short enough to still feel instant
+const TRANSITION: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BandKind {
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Band {
+    pub kind: BandKind,
+    // hz
+    pub frequency: f32,
+    // db
+    pub gain: f32,
+    pub q: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EqualizerPreset {
+    pub name: String,
+    pub bands: Vec<Band>,
+}
+
+// the bands a playing track is filtered with, picked up by the audio thread on its next frame
+#[derive(Debug, Default)]
+pub struct EqualizerControl {
+    bands: Mutex<Arc<[Band]>>,
+    version: AtomicU64,
+}
+
+impl EqualizerControl {
+    pub fn new(bands: Arc<[Band]>) -> Self {
+        Self {
+            bands: Mutex::new(bands),
+            version: AtomicU64::new(0),
+        }
+    }
+
+    // the same bands again are left alone, a fresh filter would have to settle in again
+    pub fn set(&self, bands: Arc<[Band]>) {
+        let mut current = self.bands.lock();
+
+        if **current == *bands {
+            return;
+        }
+
+        *current = bands;
+        self.version.fetch_add(1, Ordering::Release);
+    }
+}
+
+// second order filter in transposed direct form II, per the audio EQ cookbook
+#[derive(Debug, Clone)]
+struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    // one pair of delays per channel
+    state: Vec<[f64; 2]>,
+}
+
+impl Biquad {
+    fn new(band: &Band, sample_rate: u32, channels: usize) -> Self {
+        // a band at or past nyquist can't be represented, keep it just under
+        let frequency = (band.frequency as f64).clamp(10.0, sample_rate as f64 * 0.49);
+        let a = 10f64.powf(band.gain as f64 / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate as f64;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * (band.q as f64).max(0.01));
+        let shelf = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match band.kind {
+            BandKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            BandKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + shelf),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - shelf),
+                (a + 1.0) + (a - 1.0) * cos + shelf,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - shelf,
+            ),
+            BandKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + shelf),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - shelf),
+                (a + 1.0) - (a - 1.0) * cos + shelf,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - shelf,
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+            state: vec![[0.0; 2]; channels],
+        }
+    }
+
+    fn process(&mut self, channel: usize, x: f64) -> f64 {
+        let [z1, z2] = &mut self.state[channel];
+        let y = self.b0 * x + *z1;
+
+        *z1 = self.b1 * x - self.a1 * y + *z2;
+        *z2 = self.b2 * x - self.a2 * y;
+
+        y
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Chain(Vec<Biquad>);
+
+impl Chain {
+    fn new(bands: &[Band], sample_rate: u32, channels: usize) -> Self {
+        // flat bands do nothing but cost time
+        let filters = bands
+            .iter()
+            .filter(|x| x.gain != 0.0)
+            .map(|x| Biquad::new(x, sample_rate, channels))
+            .collect();
+
+        Self(filters)
+    }
+
+    fn process(&mut self, channel: usize, sample: f32) -> f32 {
+        let mut x = sample as f64;
+
+        for filter in &mut self.0 {
+            x = filter.process(channel, x);
+        }
+
+        x as f32
+    }
+
+    fn reset(&mut self) {
+        for filter in &mut self.0 {
+            filter.state.fill([0.0; 2]);
+        }
+    }
+}
+
+// a chain being faded out after the bands changed
+struct Outgoing {
+    chain: Chain,
+    // frames left of the transition
+    remaining: u32,
+}
+
+pub struct Equalizer<S> {
+    inner: S,
+    control: Arc<EqualizerControl>,
+    channels: usize,
+    sample_rate: u32,
+    version: u64,
+    chain: Chain,
+    outgoing: Option<Outgoing>,
+    transition: u32,
+    channel: usize,
+}
+
+impl<S> Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, control: Arc<EqualizerControl>) -> Self {
+        let channels = inner.channels() as usize;
+        let sample_rate = inner.sample_rate();
+        let version = control.version.load(Ordering::Acquire);
+        let chain = Chain::new(&control.bands.lock(), sample_rate, channels);
+
+        Self {
+            inner,
+            control,
+            channels,
+            sample_rate,
+            version,
+            chain,
+            outgoing: None,
+            transition: (TRANSITION.as_secs_f32() * sample_rate as f32) as u32,
+            channel: 0,
+        }
+    }
+
+    // both chains run side by side for a moment, crossfading rather than jumping
+    // between the old and new response is what keeps the switch from clicking
+    fn check_bands(&mut self) {
+        let version = self.control.version.load(Ordering::Acquire);
+
+        // cutting a fade short would be a jump of its own, bands set meanwhile wait for it
+        if version == self.version || self.outgoing.is_some() {
+            return;
+        }
+
+        // whoever is setting them will be done in a moment, the next frame tries again
+        let Some(bands) = self.control.bands.try_lock().map(|x| x.clone()) else {
+            return;
+        };
+
+        let chain = Chain::new(&bands, self.sample_rate, self.channels);
+
+        self.version = version;
+        self.outgoing = Some(Outgoing {
+            chain: std::mem::replace(&mut self.chain, chain),
+            remaining: self.transition,
+        });
+    }
+}
+
+impl<S> Iterator for Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == 0 {
+            self.check_bands();
+        }
+
+        let sample = self.inner.next()?;
+        let channel = self.channel;
+        let mut output = self.chain.process(channel, sample);
+
+        if let Some(outgoing) = &mut self.outgoing {
+            let t = 1.0 - outgoing.remaining as f32 / self.transition.max(1) as f32;
+            let old = outgoing.chain.process(channel, sample);
+
+            output = output * t + old * (1.0 - t);
+
+            if channel + 1 == self.channels {
+                outgoing.remaining = outgoing.remaining.saturating_sub(1);
+
+                if outgoing.remaining == 0 {
+                    self.outgoing = None;
+                }
+            }
+        }
+
+        self.channel = (channel + 1) % self.channels;
+
+        Some(output)
+    }
+}
+
+impl<S> Source for Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.inner.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.inner.total_duration()
+    }
+
+    // a seek is a jump anyway, old filter state would only ring on past it
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.chain.reset();
+        self.outgoing = None;
+        self.channel = 0;
+
+        Ok(())
+    }
+}
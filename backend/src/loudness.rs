@@ -0,0 +1,208 @@
+use crate::AppState;
+use crate::tracks;
+use anyhow::{Context, Result, anyhow, bail};
+use ebur128::{EbuR128, Mode};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::DecoderOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::default::{get_codecs, get_probe};
+use tauri::{AppHandle, Manager};
+
+// ReplayGain 2.0 targets -18 LUFS, so computed gains line up with tagged ones
+pub const REFERENCE_LOUDNESS: f32 = -18.0;
+
+// tracks handed to the workers per round, results are saved after each one
+const BATCH_SIZE: u32 = 32;
+
+// leaves most of the machine to playback and whatever else is running
+const WORKERS: usize = 2;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Loudness {
+    // integrated loudness in LUFS
+    pub integrated: f64,
+    // linear, 1.0 being full scale
+    pub true_peak: f64,
+}
+
+// decodes the whole file, so it's slow and meant for the background job only
+pub fn measure(path: &Path) -> Result<Loudness> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+
+    if let Some(extension) = path.extension().and_then(|x| x.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let mut probed = get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let track = probed
+        .format
+        .default_track()
+        .context("no audio track found")?;
+
+    let track_id = track.id;
+    let mut decoder = get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut meter: Option<EbuR128> = None;
+    let mut buffer: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        let packet = match probed.format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err)) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // a corrupt frame here and there shouldn't throw away the whole track
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+
+        let spec = *decoded.spec();
+
+        // the channel layout isn't always known until the first frame is decoded
+        let meter = match &mut meter {
+            Some(meter) => meter,
+            None => meter.insert(
+                EbuR128::new(
+                    spec.channels.count() as u32,
+                    spec.rate,
+                    Mode::I | Mode::TRUE_PEAK,
+                )
+                .map_err(|err| anyhow!("{err}"))?,
+            ),
+        };
+
+        // the sample buffer counts samples across all channels, the decoded one frames
+        if buffer
+            .as_ref()
+            .is_none_or(|x| x.capacity() < decoded.capacity() * spec.channels.count())
+        {
+            buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+        }
+
+        let buffer = buffer.as_mut().context("missing sample buffer")?;
+        buffer.copy_interleaved_ref(decoded);
+
+        meter
+            .add_frames_f32(buffer.samples())
+            .map_err(|err| anyhow!("{err}"))?;
+    }
+
+    let meter = meter.context("no audio decoded")?;
+    let integrated = meter.loudness_global().map_err(|err| anyhow!("{err}"))?;
+
+    // silence measures as -inf and has no meaningful gain
+    if !integrated.is_finite() {
+        bail!("track is silent");
+    }
+
+    let mut true_peak: f64 = 0.0;
+
+    for channel in 0..meter.channels() {
+        true_peak = true_peak.max(meter.true_peak(channel).map_err(|err| anyhow!("{err}"))?);
+    }
+
+    Ok(Loudness {
+        integrated,
+        true_peak,
+    })
+}
+
+// works through every track that has neither gain tags nor a measurement yet,
+// progress lives in the database so an interrupted run picks up where it left off
+#[derive(Default)]
+pub struct LoudnessAnalyzer {
+    running: AtomicBool,
+    requested: AtomicBool,
+}
+
+impl LoudnessAnalyzer {
+    // safe to call whenever tracks may have been added, extra calls while a run
+    // is going just get it to look for more work before stopping
+    pub fn request(&self, app: &AppHandle) {
+        self.requested.store(true, Ordering::SeqCst);
+
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let app = app.clone();
+
+        tauri::async_runtime::spawn(async move {
+            let state = app.state::<AppState>();
+            let analyzer = &state.analyzer;
+
+            loop {
+                while analyzer.requested.swap(false, Ordering::SeqCst) {
+                    // a pass cut short leaves the rest pending for the next request, tracks
+                    // that failed to measure wait for their file to change, see `set_loudness`
+                    _ = analyze_pending(&app).await;
+                }
+
+                analyzer.running.store(false, Ordering::SeqCst);
+
+                // a request could have landed between the last pass and clearing the flag
+                if !analyzer.requested.load(Ordering::SeqCst)
+                    || analyzer.running.swap(true, Ordering::SeqCst)
+                {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+async fn analyze_pending(app: &AppHandle) -> Result<()> {
+    let state = app.state::<AppState>();
+
+    loop {
+        let pending: Vec<(String, PathBuf)> = state.db.get_unanalyzed_tracks(BATCH_SIZE).await?;
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        let results = tokio::task::spawn_blocking(move || {
+            tracks::parallel_map(&pending, WORKERS, |(hash, path)| {
+                (hash.clone(), measure(path).ok())
+            })
+        })
+        .await??;
+
+        state.db.set_loudness(&results).await?;
+        refresh_player(app).await?;
+    }
+}
+
+// the queue's gains are looked up when it's set, so they'd miss fresh measurements otherwise
+async fn refresh_player(app: &AppHandle) -> Result<()> {
+    let state = app.state::<AppState>();
+    let paths = state.player.lock().paths();
+    let info = state.db.get_playback_info(&paths).await?;
+
+    state.player.lock().update_info(info);
+
+    Ok(())
+}
@@ -2,34 +2,42 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod covers;
 mod db;
+mod decoder;
+mod equalizer;
+mod loudness;
+mod output;
+mod playback;
 mod players;
+mod queue;
+mod tags;
+mod tempo;
 mod tracks;
 mod utils;
+mod watcher;
 
 use anyhow::Result;
 use db::Db;
+use loudness::LoudnessAnalyzer;
+use output::AudioOutput;
 use parking_lot::Mutex;
 use players::{Player, ScrubPlayer};
-use rodio::{OutputStream, Sink};
 use serde::Serialize;
-use std::sync::Arc;
-use tauri::{Builder, Emitter, Manager};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, mpsc};
+use tauri::{Builder, Emitter, Manager, RunEvent};
 use tauri_plugin_http::reqwest::Client as HttpClient;
 use tokio::runtime::Handle as RuntimeHandle;
 use tracks::Track;
+use watcher::LibraryWatcher;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // ! DO NOT DROP _stream (don't assign to just '_')
-    let (_stream, handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&handle)?;
-    let player = Arc::new(Mutex::new(Player::new(sink)?));
-
-    // ! DO NOT DROP _stream (don't assign to just '_')
-    let (_stream, handle) = OutputStream::try_default()?;
-    let sink = Sink::try_new(&handle)?;
-    let scrub_player = Arc::new(Mutex::new(ScrubPlayer::new(sink)?));
+    let (events, events_rx) = mpsc::channel();
+    let player = Arc::new(Mutex::new(Player::new(events)?));
+    let scrub_player = Arc::new(Mutex::new(ScrubPlayer::new()?));
+    let output = AudioOutput::start(player.clone(), scrub_player.clone())?;
 
     let mut builder = Builder::default();
 
@@ -78,19 +86,41 @@ async fn main() -> Result<()> {
             // could always do this from UI side but, oh well
             tokio::task::block_in_place(|| RuntimeHandle::current().block_on(db.init()))?;
 
-            if let Some(path) = std::env::args().nth(1) {
-                if let Ok(track) = Track::new(path, &covers_path) {
-                    player.lock().arbitrary_tracks.push(track);
-                }
+            if let Some(path) = std::env::args().nth(1)
+                && let Ok(track) = Track::new(path, &covers_path)
+            {
+                player.lock().arbitrary_tracks.push(track);
             }
 
+            let dirs = tokio::task::block_in_place(|| {
+                RuntimeHandle::current().block_on(db.get_scan_dirs())
+            })?;
+
+            let presets = tokio::task::block_in_place(|| {
+                RuntimeHandle::current().block_on(db.get_equalizer_presets())
+            })?;
+
+            player.lock().set_equalizer_presets(presets);
+
             app.manage(AppState {
                 http_client,
                 player,
                 scrub_player,
                 db,
+                scan_cancel: Arc::new(AtomicBool::new(false)),
+                watcher: Mutex::new(LibraryWatcher::default()),
+                analyzer: LoudnessAnalyzer::default(),
+                output,
             });
 
+            let state = app.state::<AppState>();
+            state.watcher.lock().watch(app.handle(), dirs);
+
+            players::forward_events(app.handle().clone(), events_rx);
+
+            // carries on with whatever an earlier session didn't get to
+            state.analyzer.request(app.handle());
+
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -101,9 +131,26 @@ async fn main() -> Result<()> {
             commands::player_play,
             commands::player_pause,
             commands::player_set_current,
+            commands::player_append,
+            commands::player_insert_next,
+            commands::player_remove,
+            commands::player_move,
+            commands::player_shuffle,
+            commands::player_unshuffle,
+            commands::player_set_repeat,
+            commands::player_get_next_index,
+            commands::player_get_prev_index,
             commands::player_is_paused,
             commands::player_set_volume,
+            commands::player_set_rate,
+            commands::player_set_pitch,
+            commands::player_set_normalization,
+            commands::player_set_crossfade,
+            commands::player_set_default_equalizer,
             commands::player_get_arbitrary_tracks,
+            commands::player_restore_session,
+            commands::player_get_output_devices,
+            commands::player_set_output_device,
             commands::scrub_player_start,
             commands::scrub_player_set_current,
             commands::scrub_player_seek,
@@ -128,15 +175,46 @@ async fn main() -> Result<()> {
             commands::db_get_lyrics,
             commands::db_set_lyrics,
             commands::db_set_rules,
+            commands::db_get_equalizer_presets,
+            commands::db_set_equalizer_preset,
+            commands::db_rename_equalizer_preset,
+            commands::db_remove_equalizer_preset,
+            commands::db_get_equalizer_assignment,
+            commands::db_set_track_equalizer,
+            commands::db_set_album_equalizer,
             commands::db_scan_dirs,
+            commands::db_cancel_scan,
+            commands::db_get_scan_errors,
+            commands::db_retry_scan_errors,
+            commands::db_get_watch_errors,
             commands::db_get_dirs,
             commands::db_set_dirs,
+            commands::db_get_dir_options,
+            commands::db_set_dir_options,
+            commands::db_get_excludes,
+            commands::db_set_excludes,
             commands::db_backup,
             commands::db_restore,
             commands::db_reset,
             commands::tracks_find_artist_image,
+            commands::tracks_update_tags,
+            commands::tracks_preview_tag_edit,
+            commands::tracks_apply_tag_edit,
+            commands::tracks_get_tag_edits,
+            commands::tracks_undo_tag_edit,
         ])
-        .run(tauri::generate_context!())?;
+        .build(tauri::generate_context!())?
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                let state = app.state::<AppState>();
+                let session = state.player.lock().session();
+
+                // nothing to be done about it this late, the next run just starts empty
+                _ = tokio::task::block_in_place(|| {
+                    RuntimeHandle::current().block_on(state.db.set_session(&session))
+                });
+            }
+        });
 
     Ok(())
 }
@@ -146,6 +224,10 @@ struct AppState {
     player: Arc<Mutex<Player>>,
     scrub_player: Arc<Mutex<ScrubPlayer>>,
     db: Db,
+    scan_cancel: Arc<AtomicBool>,
+    watcher: Mutex<LibraryWatcher>,
+    analyzer: LoudnessAnalyzer,
+    output: AudioOutput,
 }
 
 #[derive(Debug, Clone, Serialize)]
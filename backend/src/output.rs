@@ -0,0 +1,234 @@
+use crate::playback::Format;
+use crate::players::{Player, ScrubPlayer};
+use anyhow::{Context, Result, anyhow};
+use parking_lot::Mutex;
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::cpal::{
+    self, BuildStreamError, Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+};
+use rodio::dynamic_mixer::{self, DynamicMixer, DynamicMixerController};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+// how often to look for the system default having changed, or the chosen device
+// having come back, a device that stops working says so through its stream
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+pub fn devices() -> Result<Vec<OutputDevice>> {
+    let host = cpal::default_host();
+    let default = host.default_output_device().and_then(|x| x.name().ok());
+
+    let devices = host
+        .output_devices()?
+        .filter_map(|x| x.name().ok())
+        .map(|name| OutputDevice {
+            is_default: default.as_ref() == Some(&name),
+            name,
+        })
+        .collect();
+
+    Ok(devices)
+}
+
+enum Message {
+    SetDevice {
+        // `None` follows the system default
+        device: Option<String>,
+        reply: Sender<Result<()>>,
+    },
+    // the stream with this id stopped working, e.g. its device was unplugged
+    Failed(u64),
+}
+
+// the stream has to stay on the thread that opened it, so it lives on one of its own
+// which moves both players over whenever the device changes
+pub struct AudioOutput {
+    messages: Sender<Message>,
+}
+
+impl AudioOutput {
+    // opens the default device before returning, so the players can be used right away
+    pub fn start(
+        player: Arc<Mutex<Player>>,
+        scrub_player: Arc<Mutex<ScrubPlayer>>,
+    ) -> Result<Self> {
+        let (messages, rx) = mpsc::channel();
+        let failures = messages.clone();
+
+        thread::spawn(move || run(rx, failures, player, scrub_player));
+
+        let output = Self { messages };
+        output.set_device(None)?;
+
+        Ok(output)
+    }
+
+    // a device that goes away falls back to the default until it comes back
+    pub fn set_device(&self, device: Option<String>) -> Result<()> {
+        let (reply, rx) = mpsc::channel();
+
+        self.messages
+            .send(Message::SetDevice { device, reply })
+            .map_err(|_| anyhow!("Audio output has stopped"))?;
+
+        rx.recv()?
+    }
+}
+
+struct Opened {
+    // ! DO NOT DROP, nothing plays once it's gone
+    _stream: Stream,
+    id: u64,
+    name: String,
+    // reopened on every check until that works
+    failed: bool,
+}
+
+fn run(
+    rx: Receiver<Message>,
+    failures: Sender<Message>,
+    player: Arc<Mutex<Player>>,
+    scrub_player: Arc<Mutex<ScrubPlayer>>,
+) {
+    let mut opened: Option<Opened> = None;
+    let mut chosen: Option<String> = None;
+    let mut next_id = 0;
+
+    loop {
+        let mut reply = None;
+
+        match rx.recv_timeout(CHECK_INTERVAL) {
+            Ok(Message::SetDevice { device, reply: x }) => {
+                chosen = device;
+                reply = Some(x);
+            }
+            // a stream that has since been replaced has nothing to say anymore
+            Ok(Message::Failed(id)) => match &mut opened {
+                Some(x) if x.id == id => x.failed = true,
+                _ => continue,
+            },
+            // already on the device asked for, nothing to look for
+            Err(RecvTimeoutError::Timeout)
+                if chosen.is_some()
+                    && opened
+                        .as_ref()
+                        .is_some_and(|x| !x.failed && Some(&x.name) == chosen.as_ref()) =>
+            {
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let result = (|| {
+            let device = resolve(chosen.as_deref()).context("No output device available")?;
+            let name = device.name()?;
+
+            // already there, asking again still reopens it in case it got stuck
+            if reply.is_none() && opened.as_ref().is_some_and(|x| !x.failed && x.name == name) {
+                return Ok(());
+            }
+
+            next_id += 1;
+
+            let (stream, mixer, format) = open(&device, next_id, failures.clone())?;
+
+            // both move over before the old stream goes, so neither is left without one
+            let moved = player.lock().set_output(&mixer, format);
+            let scrub_moved = scrub_player.lock().set_output(&mixer);
+
+            opened = Some(Opened {
+                _stream: stream,
+                id: next_id,
+                name,
+                failed: false,
+            });
+
+            moved.and(scrub_moved)
+        })();
+
+        if let Some(reply) = reply {
+            _ = reply.send(result);
+        }
+    }
+}
+
+// like rodio's own stream, but one that reports back when it stops working
+fn open(
+    device: &Device,
+    id: u64,
+    failures: Sender<Message>,
+) -> Result<(Stream, Arc<DynamicMixerController<f32>>, Format)> {
+    let supported = device.default_output_config()?;
+    let format = Format::for_config(&supported);
+    let (controller, mixer) = dynamic_mixer::mixer(format.channels, format.sample_rate);
+
+    // the device is reopened or another one picked on the next check, which is all that's done
+    // about any of the reasons a stream gives up
+    let on_error = move |_| {
+        _ = failures.send(Message::Failed(id));
+    };
+
+    let config = supported.config();
+
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build::<f32>(device, &config, mixer, on_error),
+        SampleFormat::F64 => build::<f64>(device, &config, mixer, on_error),
+        SampleFormat::I8 => build::<i8>(device, &config, mixer, on_error),
+        SampleFormat::I16 => build::<i16>(device, &config, mixer, on_error),
+        SampleFormat::I32 => build::<i32>(device, &config, mixer, on_error),
+        SampleFormat::I64 => build::<i64>(device, &config, mixer, on_error),
+        SampleFormat::U8 => build::<u8>(device, &config, mixer, on_error),
+        SampleFormat::U16 => build::<u16>(device, &config, mixer, on_error),
+        SampleFormat::U32 => build::<u32>(device, &config, mixer, on_error),
+        SampleFormat::U64 => build::<u64>(device, &config, mixer, on_error),
+        _ => Err(BuildStreamError::StreamConfigNotSupported),
+    }?;
+
+    stream.play()?;
+
+    Ok((stream, controller, format))
+}
+
+fn build<T>(
+    device: &Device,
+    config: &StreamConfig,
+    mut mixer: DynamicMixer<f32>,
+    on_error: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<Stream, BuildStreamError>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for sample in data {
+                *sample = mixer.next().map_or(T::EQUILIBRIUM, T::from_sample);
+            }
+        },
+        on_error,
+        None,
+    )
+}
+
+fn resolve(chosen: Option<&str>) -> Option<Device> {
+    let host = cpal::default_host();
+
+    let device = chosen.and_then(|name| {
+        host.output_devices()
+            .ok()?
+            .find(|x| x.name().is_ok_and(|x| x == name))
+    });
+
+    device.or_else(|| host.default_output_device())
+}
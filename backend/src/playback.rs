@@ -0,0 +1,536 @@
+use crate::decoder::{SeekPosition, TrackDecoder};
+use crate::equalizer::{Equalizer, EqualizerControl};
+use anyhow::Result;
+use parking_lot::Mutex;
+use rodio::cpal::FromSample;
+use rodio::cpal::SupportedStreamConfig;
+use rodio::source::{SeekError, UniformSourceIterator};
+use rodio::{Sample, Source};
+use serde::Deserialize;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+// how often, in frames, a playback publishes its position and looks at
+// whether it's time to start fading
+const CHECK_FRAMES: u64 = 512;
+
+// every track is converted to this before playing, so switching between them
+// never changes the shape of the stream the sink sees
+#[derive(Debug, Clone, Copy)]
+pub struct Format {
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+// only stands in until an output is opened
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            channels: 2,
+            sample_rate: 44100,
+        }
+    }
+}
+
+impl Format {
+    // matches the stream so tracks are only ever resampled and remixed once
+    pub fn for_config(config: &SupportedStreamConfig) -> Self {
+        Self {
+            channels: config.channels(),
+            sample_rate: config.sample_rate().0,
+        }
+    }
+
+    // interleaved samples, always a whole number of frames
+    fn samples(&self, duration: Duration) -> u64 {
+        let frames = (duration.as_secs_f64() * self.sample_rate as f64) as u64;
+        frames * self.channels as u64
+    }
+
+    fn duration(&self, samples: u64) -> Duration {
+        let frames = samples / self.channels as u64;
+        Duration::from_secs_f64(frames as f64 / self.sample_rate as f64)
+    }
+}
+
+// linear factor a track is scaled by, shared so it can change while playing
+#[derive(Debug)]
+pub struct Gain(AtomicU32);
+
+impl Gain {
+    pub fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FadeCurve {
+    Linear,
+    // keeps the combined power steady so the middle of the fade doesn't dip
+    #[default]
+    EqualPower,
+    // eases in and out, lingering on each track a little longer
+    SCurve,
+}
+
+impl FadeCurve {
+    // level of the incoming track `t` of the way through, the outgoing one mirrors it
+    fn fade_in(self, t: f32) -> f32 {
+        match self {
+            FadeCurve::Linear => t,
+            FadeCurve::EqualPower => (t * FRAC_PI_2).sin(),
+            FadeCurve::SCurve => (1.0 - (t * PI).cos()) / 2.0,
+        }
+    }
+
+    fn fade_out(self, t: f32) -> f32 {
+        self.fade_in(1.0 - t)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Crossfade {
+    // seconds, 0 turns it off
+    pub length: f32,
+    pub curve: FadeCurve,
+}
+
+pub struct Entry {
+    pub id: u64,
+    source: Box<dyn Source<Item = f32> + Send>,
+    gain: Arc<Gain>,
+    // interleaved samples in the whole track, when it is known
+    samples: Option<u64>,
+    // where the decoder landed after a seek, sources that can't say are taken at their word
+    seeked: Option<Arc<SeekPosition>>,
+    // how to blend into this entry from the one before it, `None` plays it gapless
+    pub crossfade: Option<Crossfade>,
+}
+
+impl Entry {
+    // takes any source so a playback can be rendered offline just by iterating it,
+    // `duration` is what's known about the track when the source can't tell
+    pub fn new<S>(
+        id: u64,
+        source: S,
+        duration: Option<Duration>,
+        format: Format,
+        gain: Arc<Gain>,
+        equalizer: Arc<EqualizerControl>,
+    ) -> Self
+    where
+        S: Source + Send + 'static,
+        S::Item: Sample + Send,
+        f32: FromSample<S::Item>,
+    {
+        let source = UniformSourceIterator::new(source, format.channels, format.sample_rate);
+        let source = Equalizer::new(source, equalizer);
+        let samples = duration
+            .or_else(|| source.total_duration())
+            .map(|x| format.samples(x));
+
+        Self {
+            id,
+            source: Box::new(source),
+            gain,
+            samples,
+            seeked: None,
+            crossfade: None,
+        }
+    }
+
+    pub fn open(
+        id: u64,
+        path: impl AsRef<Path>,
+        duration: Option<Duration>,
+        format: Format,
+        gain: Arc<Gain>,
+        equalizer: Arc<EqualizerControl>,
+    ) -> Result<Self> {
+        let decoder = TrackDecoder::open(path)?;
+        let seeked = decoder.seeked();
+
+        Ok(Self {
+            seeked: Some(seeked),
+            ..Self::new(id, decoder, duration, format, gain, equalizer)
+        })
+    }
+
+    fn next_sample(&mut self) -> Option<f32> {
+        self.source.next().map(|x| x * self.gain.get())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PlaybackEvent {
+    // the queued entry took over from the current one, either once it ran out
+    // or at the start of a crossfade
+    Advanced { from: u64, to: u64 },
+    // ran out with nothing queued after it
+    Finished { id: u64 },
+}
+
+// the parts of a playback the player reaches into from outside the audio thread
+#[derive(Default)]
+pub struct Shared {
+    next: Mutex<Option<Entry>>,
+    // both in samples of the current entry, 0 for a duration the container doesn't give
+    position: AtomicU64,
+    duration: AtomicU64,
+}
+
+impl Shared {
+    // returns whatever was queued before, unless playback already took it
+    pub fn queue(&self, entry: Option<Entry>) -> Option<Entry> {
+        std::mem::replace(&mut *self.next.lock(), entry)
+    }
+
+    pub fn update_queued(&self, f: impl FnOnce(&mut Entry)) {
+        if let Some(entry) = self.next.lock().as_mut() {
+            f(entry);
+        }
+    }
+
+    pub fn position(&self, format: Format) -> Duration {
+        format.duration(self.position.load(Ordering::Relaxed))
+    }
+
+    pub fn duration(&self, format: Format) -> Option<Duration> {
+        let samples = self.duration.load(Ordering::Relaxed);
+        (samples > 0).then(|| format.duration(samples))
+    }
+}
+
+// the tail of the previous entry, mixed under the start of the current one
+struct Fade {
+    entry: Entry,
+    curve: FadeCurve,
+    // in interleaved samples
+    length: u64,
+    done: u64,
+}
+
+// a single never-changing source appended to the sink, which moves on to the
+// queued entry on its own the moment the current one runs out
+pub struct Playback {
+    current: Entry,
+    outgoing: Option<Fade>,
+    format: Format,
+    shared: Arc<Shared>,
+    events: Sender<PlaybackEvent>,
+    // samples into the current entry
+    samples: u64,
+    finished: bool,
+}
+
+impl Playback {
+    pub fn new(
+        entry: Entry,
+        format: Format,
+        shared: Arc<Shared>,
+        events: Sender<PlaybackEvent>,
+    ) -> Self {
+        shared
+            .duration
+            .store(entry.samples.unwrap_or_default(), Ordering::Relaxed);
+
+        Self {
+            current: entry,
+            outgoing: None,
+            format,
+            shared,
+            events,
+            samples: 0,
+            finished: false,
+        }
+    }
+
+    fn advance(&mut self, next: Entry) -> Entry {
+        _ = self.events.send(PlaybackEvent::Advanced {
+            from: self.current.id,
+            to: next.id,
+        });
+
+        self.shared
+            .duration
+            .store(next.samples.unwrap_or_default(), Ordering::Relaxed);
+
+        self.set_position(0);
+        std::mem::replace(&mut self.current, next)
+    }
+
+    fn set_position(&mut self, samples: u64) {
+        self.samples = samples;
+        self.shared.position.store(samples, Ordering::Relaxed);
+    }
+
+    // starts mixing in the queued entry once the current one is within its crossfade
+    fn try_crossfade(&mut self) {
+        let Some(total) = self.current.samples else {
+            return;
+        };
+
+        let remaining = total.saturating_sub(self.samples);
+
+        // never blocks the audio thread, the next check is only a few ms away
+        let Some(mut next) = self.shared.next.try_lock() else {
+            return;
+        };
+
+        let Some(crossfade) = next.as_ref().and_then(|x| x.crossfade) else {
+            return;
+        };
+
+        let length = self
+            .format
+            .samples(Duration::from_secs_f32(crossfade.length.max(0.0)));
+
+        if remaining == 0 || remaining > length {
+            return;
+        }
+
+        let Some(incoming) = next.take() else {
+            return;
+        };
+
+        drop(next);
+
+        let outgoing = self.advance(incoming);
+
+        self.outgoing = Some(Fade {
+            entry: outgoing,
+            curve: crossfade.curve,
+            length: remaining,
+            done: 0,
+        });
+    }
+}
+
+impl Iterator for Playback {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.finished {
+            return None;
+        }
+
+        let check_every = CHECK_FRAMES * self.format.channels as u64;
+
+        if self.samples.is_multiple_of(check_every) {
+            self.shared.position.store(self.samples, Ordering::Relaxed);
+
+            if self.outgoing.is_none() {
+                self.try_crossfade();
+            }
+        }
+
+        loop {
+            let Some(sample) = self.current.next_sample() else {
+                // whatever was still fading out goes with it
+                self.outgoing = None;
+
+                let Some(next) = self.shared.next.lock().take() else {
+                    self.finished = true;
+                    _ = self.events.send(PlaybackEvent::Finished {
+                        id: self.current.id,
+                    });
+
+                    return None;
+                };
+
+                self.advance(next);
+                continue;
+            };
+
+            self.samples += 1;
+
+            let Some(fade) = &mut self.outgoing else {
+                return Some(sample);
+            };
+
+            let t = fade.done as f32 / fade.length as f32;
+            let tail = fade.entry.next_sample().unwrap_or(0.0);
+            let mixed = sample * fade.curve.fade_in(t) + tail * fade.curve.fade_out(t);
+
+            fade.done += 1;
+
+            if fade.done >= fade.length {
+                self.outgoing = None;
+            }
+
+            return Some(mixed);
+        }
+    }
+}
+
+impl Source for Playback {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.format.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.format.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    // only ever seeks within the current entry, cutting a fade short
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // past the end goes to the end, the stored duration knows it even when the source doesn't
+        let pos = match self.current.samples {
+            Some(total) => pos.min(self.format.duration(total)),
+            None => pos,
+        };
+
+        self.current.source.try_seek(pos)?;
+
+        let landed = self.current.seeked.as_ref().map_or(pos, |x| x.get());
+
+        self.outgoing = None;
+        self.set_position(self.format.samples(landed));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+    use std::sync::mpsc;
+
+    // the checks every 512 frames land on whole half seconds, so a fade starts right on time
+    const FORMAT: Format = Format {
+        channels: 1,
+        sample_rate: 1024,
+    };
+
+    // like a VBR stream whose container doesn't say how long it is
+    struct Unsized(std::vec::IntoIter<f32>);
+
+    impl Iterator for Unsized {
+        type Item = f32;
+
+        fn next(&mut self) -> Option<f32> {
+            self.0.next()
+        }
+    }
+
+    impl Source for Unsized {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+
+        fn channels(&self) -> u16 {
+            FORMAT.channels
+        }
+
+        fn sample_rate(&self) -> u32 {
+            FORMAT.sample_rate
+        }
+
+        fn total_duration(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    fn entry<S>(id: u64, source: S, duration: Option<Duration>) -> Entry
+    where
+        S: Source + Send + 'static,
+        S::Item: Sample + Send,
+        f32: FromSample<S::Item>,
+    {
+        let equalizer = Arc::new(EqualizerControl::new(Arc::from([])));
+        Entry::new(
+            id,
+            source,
+            duration,
+            FORMAT,
+            Arc::new(Gain::new(1.0)),
+            equalizer,
+        )
+    }
+
+    // 4s of 1.0 fading into 4s of 2.0 over the last second of the first
+    fn render(first: Entry, curve: FadeCurve) -> (Vec<f32>, Vec<PlaybackEvent>) {
+        let mut second = entry(1, SamplesBuffer::new(1, 1024, vec![2.0; 4096]), None);
+        second.crossfade = Some(Crossfade { length: 1.0, curve });
+
+        let shared = Arc::new(Shared::default());
+        shared.queue(Some(second));
+
+        let (tx, rx) = mpsc::channel();
+        let output = Playback::new(first, FORMAT, shared, tx).collect();
+
+        (output, rx.try_iter().collect())
+    }
+
+    #[test]
+    fn crossfades_over_the_configured_length() {
+        let first = entry(0, SamplesBuffer::new(1, 1024, vec![1.0; 4096]), None);
+        let (output, events) = render(first, FadeCurve::Linear);
+
+        // the second track starts a second before the first one ends
+        assert_eq!(output.len(), 4096 + 4096 - 1024);
+        assert!(matches!(
+            events[0],
+            PlaybackEvent::Advanced { from: 0, to: 1 }
+        ));
+
+        assert!(output[..3072].iter().all(|&x| x == 1.0));
+        assert!(output[4096..].iter().all(|&x| x == 2.0));
+
+        for (i, &sample) in output[3072..4096].iter().enumerate() {
+            let t = i as f32 / 1024.0;
+            assert!(
+                (sample - (2.0 * t + (1.0 - t))).abs() < 1e-4,
+                "{i}: {sample}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_the_combined_power_steady_with_equal_power() {
+        let first = entry(0, SamplesBuffer::new(1, 1024, vec![1.0; 4096]), None);
+        let (output, _) = render(first, FadeCurve::EqualPower);
+
+        for (i, &sample) in output[3072..4096].iter().enumerate() {
+            let t = i as f32 / 1024.0;
+            let expected = 2.0 * (t * FRAC_PI_2).sin() + (t * FRAC_PI_2).cos();
+            assert!((sample - expected).abs() < 1e-4, "{i}: {sample}");
+        }
+    }
+
+    #[test]
+    fn crossfades_sources_without_a_duration_by_the_stored_one() {
+        let first = entry(
+            0,
+            Unsized(vec![1.0; 4096].into_iter()),
+            Some(Duration::from_secs(4)),
+        );
+
+        let (output, _) = render(first, FadeCurve::Linear);
+
+        assert_eq!(output.len(), 4096 + 4096 - 1024);
+    }
+}
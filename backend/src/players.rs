@@ -1,47 +1,324 @@
-use crate::tracks::Track;
-use anyhow::{Result, anyhow};
-use rodio::{Decoder, Sink};
-use std::fs::File;
-use std::io::BufReader;
+use crate::AppState;
+use crate::decoder::{SeekPosition, TrackDecoder};
+use crate::equalizer::{Band, EqualizerControl, EqualizerPreset};
+use crate::playback::{Crossfade, Entry, Format, Gain, Playback, PlaybackEvent, Shared};
+use crate::queue::{Queue, QueueEntry, QueueState, Removed, Repeat, Session};
+use crate::tempo::{Tempo, TempoControl};
+use crate::tracks::{PlaybackInfo, ReplayGain, Track};
+use anyhow::{Context, Result, anyhow};
+use rodio::Sink;
+use rodio::dynamic_mixer::DynamicMixerController;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
 
-// ? TODO: send position to frontend
+// how often the frontend hears where playback is
+const STATUS_INTERVAL: Duration = Duration::from_millis(250);
+
+// how often the session is saved besides on track changes, so a crash loses little
+const SESSION_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Normalization {
+    pub mode: NormalizationMode,
+    // dB added on top of the stored gain
+    pub preamp: f32,
+    // caps the gain so the stored peak never goes over full scale
+    pub prevent_clipping: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            mode: NormalizationMode::Off,
+            preamp: 0.0,
+            prevent_clipping: true,
+        }
+    }
+}
+
+impl Normalization {
+    // linear factor to scale the output of a track by
+    pub fn factor(&self, gain: &ReplayGain) -> f32 {
+        // falls back to the other kind when a file only carries one of them
+        let (db, peak) = match self.mode {
+            NormalizationMode::Off => return 1.0,
+            NormalizationMode::Track => (
+                gain.track_gain.or(gain.album_gain),
+                gain.track_peak.or(gain.album_peak),
+            ),
+            NormalizationMode::Album => (
+                gain.album_gain.or(gain.track_gain),
+                gain.album_peak.or(gain.track_peak),
+            ),
+        };
+
+        // untagged tracks are left alone rather than guessed at
+        let Some(db) = db else {
+            return 1.0;
+        };
+
+        let mut factor = 10f32.powf((db + self.preamp) / 20.0);
+
+        if self.prevent_clipping
+            && let Some(peak) = peak
+            && peak > 0.0
+        {
+            factor = factor.min(1.0 / peak);
+        }
+
+        factor
+    }
+}
+
+// a track that has been handed to the playback, either playing or queued after it
+struct Loaded {
+    index: usize,
+    path: PathBuf,
+    gain: Arc<Gain>,
+    equalizer: Arc<EqualizerControl>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PlayerChange {
+    // the next track took over without a gap or by fading in, carries queue indexes
+    TrackChanged { ended: usize, current: usize },
+    // the last track ran out with nothing queued after it
+    Ended { ended: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayerStatus {
+    // `None` while nothing is loaded
+    pub index: Option<usize>,
+    // seconds
+    pub position: f64,
+    pub duration: Option<f64>,
+    pub paused: bool,
+}
+
+// a saved queue lined back up, for the frontend to resume from
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RestoredSession {
+    // in queue order
+    pub tracks: Vec<Track>,
+    pub state: QueueState,
+    // milliseconds into the current track
+    pub position: u64,
+    pub volume: f32,
+}
 
 pub struct Player {
     sink: Sink,
-    current: usize,
-    pub queue: Vec<PathBuf>,
+    format: Format,
+    events: Sender<PlaybackEvent>,
+    shared: Arc<Shared>,
+    next_id: u64,
+    current_id: Option<u64>,
+    loaded: HashMap<u64, Loaded>,
+    normalization: Normalization,
+    crossfade: Crossfade,
+    tempo: Arc<TempoControl>,
+    presets: HashMap<String, Arc<[Band]>>,
+    // for tracks without a preset of their own or their album's, `None` plays them flat
+    default_equalizer: Option<String>,
+    info: HashMap<PathBuf, PlaybackInfo>,
+    queue: Queue,
     pub arbitrary_tracks: Vec<Track>,
 }
 
 impl Player {
-    pub fn new(sink: Sink) -> Result<Self> {
+    // silent until it's given an output
+    pub fn new(events: Sender<PlaybackEvent>) -> Result<Self> {
+        let (sink, _) = Sink::new_idle();
         sink.pause();
 
         Ok(Self {
             sink,
-            current: 0,
-            queue: vec![],
+            format: Format::default(),
+            events,
+            shared: Arc::default(),
+            next_id: 0,
+            current_id: None,
+            loaded: HashMap::new(),
+            normalization: Normalization::default(),
+            crossfade: Crossfade::default(),
+            tempo: Arc::default(),
+            presets: HashMap::new(),
+            default_equalizer: None,
+            info: HashMap::new(),
+            queue: Queue::default(),
             arbitrary_tracks: vec![],
         })
     }
 
+    // carries on from the same spot, playing or paused, on the new output
+    pub fn set_output(
+        &mut self,
+        mixer: &DynamicMixerController<f32>,
+        format: Format,
+    ) -> Result<()> {
+        let (sink, output) = Sink::new_idle();
+        mixer.add(output);
+        let resume = self.current_id.map(|_| self.shared.position(self.format));
+
+        sink.set_volume(self.sink.volume());
+
+        if self.sink.is_paused() {
+            sink.pause();
+        }
+
+        self.sink.stop();
+        self.sink = sink;
+        self.format = format;
+
+        if let Some(position) = resume {
+            self.goto(self.queue.current())?;
+            self.seek(position)?;
+        }
+
+        Ok(())
+    }
+
     pub fn goto(&mut self, index: usize) -> Result<()> {
-        self.set_current(index)?;
+        self.queue.set_current(index)?;
         self.stop();
 
-        self.sink.load(&self.queue[self.current])
+        let entry = self.open(index)?;
+
+        self.shared = Arc::default();
+        self.current_id = Some(entry.id);
+        let playback = Playback::new(entry, self.format, self.shared.clone(), self.events.clone());
+
+        self.sink.append(Tempo::new(playback, self.tempo.clone()));
+
+        self.sync_next();
+
+        Ok(())
+    }
+
+    // lines up whatever the queue says comes next, so playback carries on by itself
+    // once the current track ends, called after anything that could change it
+    fn sync_next(&mut self) {
+        if self.current_id.is_none() {
+            return;
+        }
+
+        // a file that can't be opened ends playback there, the frontend takes over
+        let entry = match self.queue.next_index() {
+            Some(index) => self.open(index).ok().map(|mut entry| {
+                entry.crossfade = self.crossfade_between(self.queue.current(), index);
+                entry
+            }),
+            None => None,
+        };
+
+        // a replaced entry never plays, one that was already taken still reports its start
+        if let Some(replaced) = self.shared.queue(entry) {
+            self.loaded.remove(&replaced.id);
+        }
     }
 
-    pub fn seek(&self, elapsed: u64) -> Result<()> {
+    fn open(&mut self, index: usize) -> Result<Entry> {
+        let path = self
+            .queue
+            .get(index)
+            .context("Index out of bounds")?
+            .path
+            .clone();
+
+        let gain = Arc::new(Gain::new(self.gain(&path)));
+        let equalizer = Arc::new(EqualizerControl::new(self.bands(&path)));
+        // the stored duration is exact, containers of VBR files often don't say or guess
+        let duration = self
+            .info
+            .get(&path)
+            .map(|x| Duration::from_millis(x.duration))
+            .filter(|x| !x.is_zero());
+
+        let entry = Entry::open(
+            self.next_id,
+            &path,
+            duration,
+            self.format,
+            gain.clone(),
+            equalizer.clone(),
+        )?;
+
+        self.loaded.insert(
+            self.next_id,
+            Loaded {
+                index,
+                path,
+                gain,
+                equalizer,
+            },
+        );
+        self.next_id += 1;
+
+        Ok(entry)
+    }
+
+    // called for every event the playback sends from the audio thread
+    pub fn handle(&mut self, event: PlaybackEvent) -> Option<PlayerChange> {
+        match event {
+            PlaybackEvent::Advanced { from, to } if self.current_id == Some(from) => {
+                let ended = self.queue.current();
+                let index = self.loaded.get(&to)?.index;
+
+                self.queue.set_current(index).ok()?;
+                self.loaded.remove(&from);
+                self.current_id = Some(to);
+                self.sync_next();
+
+                Some(PlayerChange::TrackChanged {
+                    ended,
+                    current: self.queue.current(),
+                })
+            }
+            PlaybackEvent::Finished { id } if self.current_id == Some(id) => {
+                self.loaded.clear();
+                self.current_id = None;
+
+                Some(PlayerChange::Ended {
+                    ended: self.queue.current(),
+                })
+            }
+            // left over from a playback that has since been replaced
+            _ => None,
+        }
+    }
+
+    // returns where the decoder landed, the end of the track when asked to go past it
+    pub fn seek(&self, position: Duration) -> Result<Duration> {
         self.sink
-            .try_seek(Duration::from_secs(elapsed))
-            .map_err(|err| anyhow!("{err}"))
+            .try_seek(position)
+            .map_err(|err| anyhow!("{err}"))?;
+
+        Ok(self.shared.position(self.format))
     }
 
-    pub fn stop(&self) {
+    pub fn stop(&mut self) {
         self.sink.stop();
+        self.loaded.clear();
+        self.current_id = None;
     }
 
     pub fn play(&self) {
@@ -52,65 +329,455 @@ impl Player {
         self.sink.pause();
     }
 
-    pub fn set_queue(&mut self, queue: Vec<PathBuf>) {
-        self.queue = queue;
+    pub fn set_queue(&mut self, queue: Vec<QueueEntry>, info: HashMap<PathBuf, PlaybackInfo>) {
+        self.queue.set(queue);
+        self.set_info(info);
+        self.sync_next();
     }
 
-    pub fn set_current(&mut self, index: usize) -> Result<()> {
-        // ? 0 is allowed as a valid default index
-        // ? so bounds check can be > queue length
-        if index > self.queue.len() {
-            Err(anyhow!("Index out of bounds"))
-        } else if index == self.current {
-            Ok(())
+    fn set_info(&mut self, info: HashMap<PathBuf, PlaybackInfo>) {
+        self.info = info;
+
+        // arbitrary tracks aren't in the library but were probed on open
+        for track in &self.arbitrary_tracks {
+            self.info
+                .entry(track.path.clone())
+                .or_insert_with(|| PlaybackInfo::from(track));
+        }
+    }
+
+    pub fn session(&self) -> Session {
+        let position = match self.current_id {
+            Some(_) => self.shared.position(self.format).as_millis() as u64,
+            None => 0,
+        };
+
+        Session {
+            position,
+            volume: self.sink.volume(),
+            ..self.queue.session()
+        }
+    }
+
+    // lines the queue back up without loading anything, the frontend goes to the
+    // current track itself so it can fall back to the web player like always
+    pub fn restore(
+        &mut self,
+        session: &Session,
+        tracks: Vec<Track>,
+        info: HashMap<PathBuf, PlaybackInfo>,
+    ) -> RestoredSession {
+        let entries = tracks
+            .iter()
+            .map(|x| {
+                let entry = QueueEntry {
+                    hash: x.hash.clone(),
+                    path: x.path.clone(),
+                };
+
+                (x.hash.clone(), entry)
+            })
+            .collect();
+
+        self.stop();
+        self.queue.restore(session, &entries);
+        self.set_info(info);
+        self.set_volume(session.volume);
+
+        let mut by_hash: HashMap<String, Track> =
+            tracks.into_iter().map(|x| (x.hash.clone(), x)).collect();
+
+        let tracks = self
+            .queue
+            .entries()
+            .iter()
+            .filter_map(|x| by_hash.remove(&x.hash))
+            .collect();
+
+        // the position belongs to a track that might not have made it back
+        let resumed = self.queue.get(self.queue.current()).map(|x| &x.hash)
+            == session.hashes.get(session.current);
+
+        RestoredSession {
+            tracks,
+            state: self.queue.state(),
+            position: if resumed { session.position } else { 0 },
+            volume: session.volume,
+        }
+    }
+
+    pub fn append(
+        &mut self,
+        entries: Vec<QueueEntry>,
+        info: HashMap<PathBuf, PlaybackInfo>,
+    ) -> QueueState {
+        self.info.extend(info);
+        self.queue.append(entries);
+        self.sync_next();
+
+        self.queue.state()
+    }
+
+    pub fn shuffle(&mut self, seed: Option<u32>) -> QueueState {
+        self.queue.shuffle(seed);
+        self.sync_next();
+
+        self.queue.state()
+    }
+
+    pub fn unshuffle(&mut self) -> QueueState {
+        self.queue.unshuffle();
+        self.sync_next();
+
+        self.queue.state()
+    }
+
+    // removing the playing track moves on to the one that took its place
+    pub fn remove(&mut self, mut indexes: Vec<usize>) -> Result<QueueState> {
+        // from the back, so the indexes still to go keep pointing at the same tracks
+        indexes.sort_unstable_by(|a, b| b.cmp(a));
+        indexes.dedup();
+
+        let mut removed = Removed::Other;
+
+        for index in indexes {
+            // going from the back, once the current track goes anything removed after
+            // that is either before it or, when it was the last one, also at the end
+            match self.queue.remove(index)? {
+                Removed::Other => {}
+                x => removed = x,
+            }
+        }
+
+        if removed == Removed::Other || self.current_id.is_none() {
+            self.sync_next();
+        } else if removed == Removed::Last {
+            self.stop();
         } else {
-            self.current = index;
-            Ok(())
+            self.goto(self.queue.current())?;
         }
+
+        Ok(self.queue.state())
+    }
+
+    pub fn move_entry(&mut self, from: usize, to: usize) -> Result<QueueState> {
+        self.queue.move_entry(from, to)?;
+        self.sync_next();
+
+        Ok(self.queue.state())
+    }
+
+    pub fn insert_next(
+        &mut self,
+        entries: Vec<QueueEntry>,
+        info: HashMap<PathBuf, PlaybackInfo>,
+    ) -> QueueState {
+        self.info.extend(info);
+        self.queue.insert_next(entries);
+        self.sync_next();
+
+        self.queue.state()
+    }
+
+    pub fn set_repeat(&mut self, repeat: Option<Repeat>) {
+        self.queue.set_repeat(repeat);
+        self.sync_next();
+    }
+
+    pub fn next_index(&self) -> Option<usize> {
+        self.queue.next_index()
+    }
+
+    pub fn prev_index(&self) -> Option<usize> {
+        self.queue.prev_index()
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.queue
+            .entries()
+            .iter()
+            .map(|x| x.path.clone())
+            .collect()
+    }
+
+    // the playing track stays as it is, e.g. when it moved within the queue
+    pub fn set_current(&mut self, index: usize) -> Result<()> {
+        self.queue.set_current(index)?;
+        self.sync_next();
+
+        Ok(())
     }
 
     pub fn is_paused(&self) -> bool {
         self.sink.is_paused()
     }
 
+    pub fn status(&self) -> PlayerStatus {
+        let loaded = self.current_id.is_some();
+
+        PlayerStatus {
+            index: loaded.then_some(self.queue.current()),
+            position: if loaded {
+                self.shared.position(self.format).as_secs_f64()
+            } else {
+                0.0
+            },
+            duration: self
+                .shared
+                .duration(self.format)
+                .filter(|_| loaded)
+                .map(|x| x.as_secs_f64()),
+            paused: self.is_paused(),
+        }
+    }
+
+    // only reaches the tracks queued up, changing the level mid-track would be jarring
+    pub fn update_info(&mut self, info: HashMap<PathBuf, PlaybackInfo>) {
+        self.info.extend(info);
+        self.apply_gains(false);
+        self.apply_equalizers();
+    }
+
     pub fn set_volume(&self, volume: f32) {
         self.sink.set_volume(volume);
     }
 
-    // pub fn set_speed(&self, speed: f32) {
-    //     self.sink.set_speed(speed);
-    // }
+    // takes effect on the playing track right away
+    pub fn set_normalization(&mut self, normalization: Normalization) {
+        self.normalization = normalization;
+        self.apply_gains(true);
+    }
+
+    fn apply_gains(&self, include_current: bool) {
+        for (id, loaded) in &self.loaded {
+            if !include_current && self.current_id == Some(*id) {
+                continue;
+            }
+
+            loaded.gain.set(self.gain(&loaded.path));
+        }
+    }
+
+    fn gain(&self, path: &Path) -> f32 {
+        self.info
+            .get(path)
+            .map(|info| self.normalization.factor(&info.gain))
+            .unwrap_or(1.0)
+    }
+
+    pub fn set_equalizer_presets(&mut self, presets: Vec<EqualizerPreset>) {
+        self.presets = presets
+            .into_iter()
+            .map(|x| (x.name, Arc::from(x.bands)))
+            .collect();
+
+        self.apply_equalizers();
+    }
+
+    pub fn set_default_equalizer(&mut self, name: Option<String>) {
+        self.default_equalizer = name;
+        self.apply_equalizers();
+    }
+
+    // unlike the gain, this reaches the playing track too, the equalizer fades
+    // from the old bands to the new ones on its own
+    fn apply_equalizers(&self) {
+        for loaded in self.loaded.values() {
+            loaded.equalizer.set(self.bands(&loaded.path));
+        }
+    }
+
+    fn bands(&self, path: &Path) -> Arc<[Band]> {
+        self.info
+            .get(path)
+            .and_then(|x| x.equalizer.as_ref())
+            .or(self.default_equalizer.as_ref())
+            .and_then(|x| self.presets.get(x))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // applies from the next automatic move on, including the one already lined up
+    pub fn set_crossfade(&mut self, crossfade: Crossfade) {
+        self.crossfade = crossfade;
+
+        self.shared.update_queued(|entry| {
+            entry.crossfade = self
+                .loaded
+                .get(&entry.id)
+                .and_then(|x| self.crossfade_between(self.queue.current(), x.index));
+        });
+    }
+
+    fn crossfade_between(&self, from: usize, to: usize) -> Option<Crossfade> {
+        if self.crossfade.length <= 0.0 {
+            return None;
+        }
+
+        let info = |index: usize| self.queue.get(index).and_then(|x| self.info.get(&x.path));
+
+        // albums meant to flow from one track into the next stay gapless
+        if let (Some(from), Some(to)) = (info(from), info(to))
+            && from.album.is_some()
+            && from.album == to.album
+            && let (Some(a), Some(b)) = (from.track_number, to.track_number)
+            && b == a + 1
+        {
+            return None;
+        }
+
+        Some(self.crossfade)
+    }
+
+    // 1.0 plays at normal speed, the pitch stays where it is
+    pub fn set_rate(&self, rate: f32) {
+        self.tempo.set_rate(rate);
+    }
+
+    // in semitones, the speed stays where it is
+    pub fn set_pitch(&self, pitch: f32) {
+        self.tempo.set_pitch(pitch);
+    }
+}
+
+// relays what the playback reports from the audio thread to the frontend, along
+// with a steady status so the frontend never has to keep its own clock, and keeps
+// the saved session from falling behind
+pub fn forward_events(app: AppHandle, events: Receiver<PlaybackEvent>) {
+    thread::spawn(move || {
+        let mut last_status = None;
+        let mut next_status = Instant::now();
+        let mut last_session = None;
+        let mut next_session = Instant::now() + SESSION_INTERVAL;
+
+        loop {
+            let timeout = next_status.saturating_duration_since(Instant::now());
+            let state = app.state::<AppState>();
+
+            match events.recv_timeout(timeout) {
+                Ok(event) => {
+                    let change = state.player.lock().handle(event);
+
+                    if change.is_some() {
+                        next_session = Instant::now();
+                    }
+
+                    match change {
+                        Some(PlayerChange::TrackChanged { ended, current }) => {
+                            _ = app.emit("player-track-ended", ended);
+                            _ = app.emit("player-track-changed", current);
+                        }
+                        Some(PlayerChange::Ended { ended }) => {
+                            _ = app.emit("player-track-ended", ended);
+                            _ = app.emit("player-ended", ());
+                        }
+                        None => {}
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if Instant::now() >= next_session {
+                next_session = Instant::now() + SESSION_INTERVAL;
+                save_session(&app, &mut last_session);
+            }
+
+            if Instant::now() < next_status {
+                continue;
+            }
+
+            next_status = Instant::now() + STATUS_INTERVAL;
+
+            // nothing new to say while paused or idle
+            let status = state.player.lock().status();
+
+            if last_status != Some(status) {
+                _ = app.emit("player-status", status);
+                last_status = Some(status);
+            }
+        }
+    });
+}
+
+// skipped while nothing changed, e.g. paused or idle
+fn save_session(app: &AppHandle, last: &mut Option<Session>) {
+    let state = app.state::<AppState>();
+    let session = state.player.lock().session();
+
+    if last.as_ref() == Some(&session) {
+        return;
+    }
+
+    // tried again on the next round if it fails, since `last` stays as it was
+    if tauri::async_runtime::block_on(state.db.set_session(&session)).is_ok() {
+        *last = Some(session);
+    }
 }
 
 pub struct ScrubPlayer {
     sink: Sink,
     current: Option<PathBuf>,
+    seeked: Option<Arc<SeekPosition>>,
 }
 
 impl ScrubPlayer {
-    pub fn new(sink: Sink) -> Result<Self> {
+    // silent until it's given an output
+    pub fn new() -> Result<Self> {
+        let (sink, _) = Sink::new_idle();
         sink.pause();
 
         Ok(Self {
             sink,
             current: None,
+            seeked: None,
         })
     }
 
+    pub fn set_output(&mut self, mixer: &DynamicMixerController<f32>) -> Result<()> {
+        let (sink, output) = Sink::new_idle();
+        mixer.add(output);
+        let resume = (!self.sink.empty()).then(|| self.sink.get_pos());
+
+        sink.set_volume(self.sink.volume());
+
+        if self.sink.is_paused() {
+            sink.pause();
+        }
+
+        self.sink.stop();
+        self.sink = sink;
+
+        if let Some(position) = resume {
+            self.start()?;
+            self.seek(position)?;
+        }
+
+        Ok(())
+    }
+
     pub fn start(&mut self) -> Result<()> {
         self.stop();
 
         if let Some(path) = &self.current {
-            self.sink.load(path)
+            let decoder = TrackDecoder::open(path)?;
+
+            self.seeked = Some(decoder.seeked());
+            self.sink.append(decoder);
+
+            Ok(())
         } else {
             Err(anyhow!("No track selected"))
         }
     }
 
-    pub fn seek(&self, elapsed: u64) -> Result<()> {
+    // returns where the decoder landed, the end of the track when asked to go past it
+    pub fn seek(&self, position: Duration) -> Result<Duration> {
         self.sink
-            .try_seek(Duration::from_secs(elapsed))
-            .map_err(|err| anyhow!("{err}"))
+            .try_seek(position)
+            .map_err(|err| anyhow!("{err}"))?;
+
+        Ok(self.seeked.as_ref().map_or(position, |x| x.get()))
     }
 
     pub fn set_current(&mut self, path: Option<PathBuf>) {
@@ -129,19 +796,3 @@ impl ScrubPlayer {
         self.sink.pause();
     }
 }
-
-trait SinkExt {
-    fn load(&self, path: impl AsRef<Path>) -> Result<()>;
-}
-
-impl SinkExt for Sink {
-    fn load(&self, path: impl AsRef<Path>) -> Result<()> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let source = Decoder::new(reader)?;
-
-        self.append(source);
-
-        Ok(())
-    }
-}
@@ -0,0 +1,537 @@
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// the frontend knows tracks by hash, playback only needs the file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueEntry {
+    pub hash: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(rename_all = "snake_case")]
+pub enum Repeat {
+    // plays the same track over and over
+    Current,
+    // goes back to the start after the last track
+    All,
+}
+
+struct Shuffle {
+    seed: u32,
+    // the order from before shuffling, to go back to
+    original: Vec<QueueEntry>,
+}
+
+// what the frontend needs to line its own list of tracks up with the queue
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueState {
+    pub hashes: Vec<String>,
+    pub current: usize,
+    pub repeat: Option<Repeat>,
+    // `None` while not shuffled
+    pub shuffle_seed: Option<u32>,
+}
+
+// what's saved along the way and on exit so the next run can pick up where this one stopped
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    pub hashes: Vec<String>,
+    // the order to unshuffle to, `None` while not shuffled
+    pub original: Option<Vec<String>>,
+    pub shuffle_seed: Option<u32>,
+    pub current: usize,
+    pub repeat: Option<Repeat>,
+    // milliseconds into the current track
+    pub position: u64,
+    pub volume: f32,
+}
+
+// what removing an entry did to the current track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Removed {
+    // some other track, the current one stays as it is
+    Other,
+    // the current track, the one after it took its place
+    Current,
+    // the current track, with nothing after it to take its place
+    Last,
+}
+
+#[derive(Default)]
+pub struct Queue {
+    entries: Vec<QueueEntry>,
+    current: usize,
+    repeat: Option<Repeat>,
+    shuffle: Option<Shuffle>,
+}
+
+impl Queue {
+    pub fn entries(&self) -> &[QueueEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, index: usize) -> Option<&QueueEntry> {
+        self.entries.get(index)
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    // starts over with a new list, forgetting any shuffle
+    pub fn set(&mut self, entries: Vec<QueueEntry>) {
+        self.entries = entries;
+        self.current = 0;
+        self.shuffle = None;
+    }
+
+    pub fn set_current(&mut self, index: usize) -> Result<()> {
+        // ? 0 is allowed as a valid default index
+        // ? so bounds check can be > queue length
+        if index > self.entries.len() {
+            return Err(anyhow!("Index out of bounds"));
+        }
+
+        self.current = index;
+
+        Ok(())
+    }
+
+    pub fn set_repeat(&mut self, repeat: Option<Repeat>) {
+        self.repeat = repeat;
+    }
+
+    // where to go once the current track ends, `None` to stop
+    pub fn next_index(&self) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        match self.repeat {
+            Some(Repeat::Current) => Some(self.current),
+            _ if self.current + 1 < self.entries.len() => Some(self.current + 1),
+            Some(Repeat::All) => Some(0),
+            None => None,
+        }
+    }
+
+    pub fn prev_index(&self) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        match self.repeat {
+            Some(Repeat::Current) => Some(self.current),
+            _ if self.current > 0 => Some(self.current - 1),
+            Some(Repeat::All) => Some(self.entries.len() - 1),
+            None => None,
+        }
+    }
+
+    // the current track moves to the front and everything else follows it in a
+    // random order, the same seed always giving the same order
+    pub fn shuffle(&mut self, seed: Option<u32>) {
+        let seed = seed.unwrap_or_else(random_seed);
+
+        // shuffling again keeps the order from before the first shuffle
+        let original = match self.shuffle.take() {
+            Some(shuffle) => shuffle.original,
+            None => self.entries.clone(),
+        };
+
+        let mut rest = std::mem::take(&mut self.entries);
+        let mut rng = SplitMix64(seed as u64);
+
+        if self.current < rest.len() {
+            self.entries.push(rest.remove(self.current));
+        }
+
+        for i in (1..rest.len()).rev() {
+            let j = (rng.next() % (i as u64 + 1)) as usize;
+            rest.swap(i, j);
+        }
+
+        self.entries.extend(rest);
+        self.current = 0;
+        self.shuffle = Some(Shuffle { seed, original });
+    }
+
+    // goes back to the original order, staying on the current track
+    pub fn unshuffle(&mut self) {
+        let Some(shuffle) = self.shuffle.take() else {
+            return;
+        };
+
+        let current = self.entries.get(self.current).map(|x| x.hash.clone());
+
+        self.entries = shuffle.original;
+        self.current = current
+            .and_then(|hash| self.entries.iter().position(|x| x.hash == hash))
+            .unwrap_or(0);
+    }
+
+    // tracks already in the queue are skipped, new ones also go at the end of
+    // the original order so they're still there after unshuffling
+    pub fn append(&mut self, entries: Vec<QueueEntry>) {
+        for entry in entries {
+            if self.entries.iter().any(|x| x.hash == entry.hash) {
+                continue;
+            }
+
+            if let Some(shuffle) = &mut self.shuffle {
+                shuffle.original.push(entry.clone());
+            }
+
+            self.entries.push(entry);
+        }
+    }
+
+    // the one that takes the place of a removed current track becomes current
+    pub fn remove(&mut self, index: usize) -> Result<Removed> {
+        if index >= self.entries.len() {
+            return Err(anyhow!("Index out of bounds"));
+        }
+
+        let removed = self.entries.remove(index);
+
+        if let Some(shuffle) = &mut self.shuffle
+            && let Some(position) = shuffle.original.iter().position(|x| x.hash == removed.hash)
+        {
+            shuffle.original.remove(position);
+        }
+
+        if index < self.current {
+            self.current -= 1;
+        }
+
+        if index != self.current {
+            return Ok(Removed::Other);
+        }
+
+        if index < self.entries.len() {
+            return Ok(Removed::Current);
+        }
+
+        // nothing took its place, current has to stay in bounds all the same
+        self.current = self.entries.len().saturating_sub(1);
+
+        Ok(Removed::Last)
+    }
+
+    // only changes the playing order, the order to unshuffle to stays as it was
+    pub fn move_entry(&mut self, from: usize, to: usize) -> Result<()> {
+        if from >= self.entries.len() || to >= self.entries.len() {
+            return Err(anyhow!("Index out of bounds"));
+        }
+
+        let entry = self.entries.remove(from);
+        self.entries.insert(to, entry);
+
+        if from == self.current {
+            self.current = to;
+        } else if from < self.current && to >= self.current {
+            self.current -= 1;
+        } else if from > self.current && to <= self.current {
+            self.current += 1;
+        }
+
+        Ok(())
+    }
+
+    // tracks already in the queue are moved rather than added twice
+    pub fn insert_next(&mut self, entries: Vec<QueueEntry>) {
+        let current = self.entries.get(self.current).map(|x| x.hash.clone());
+        let entries: Vec<QueueEntry> = entries
+            .into_iter()
+            .filter(|x| current.as_ref() != Some(&x.hash))
+            .collect();
+
+        if let Some(shuffle) = &mut self.shuffle {
+            place_after(&mut shuffle.original, current.as_deref(), &entries);
+        }
+
+        self.current = place_after(&mut self.entries, current.as_deref(), &entries);
+    }
+
+    // tracks that are gone since the session was saved are left out
+    pub fn restore(&mut self, session: &Session, entries: &HashMap<String, QueueEntry>) {
+        let resolve = |hashes: &[String]| -> Vec<QueueEntry> {
+            hashes
+                .iter()
+                .filter_map(|x| entries.get(x))
+                .cloned()
+                .collect()
+        };
+
+        let current = session.hashes.get(session.current);
+
+        self.entries = resolve(&session.hashes);
+        self.current = current
+            .and_then(|hash| self.entries.iter().position(|x| &x.hash == hash))
+            .unwrap_or(0);
+        self.repeat = session.repeat;
+        self.shuffle =
+            session
+                .original
+                .as_deref()
+                .zip(session.shuffle_seed)
+                .map(|(original, seed)| Shuffle {
+                    seed,
+                    original: resolve(original),
+                });
+    }
+
+    // leaves the parts the queue doesn't know about to the player
+    pub fn session(&self) -> Session {
+        let hashes = |entries: &[QueueEntry]| entries.iter().map(|x| x.hash.clone()).collect();
+
+        Session {
+            hashes: hashes(&self.entries),
+            original: self.shuffle.as_ref().map(|x| hashes(&x.original)),
+            shuffle_seed: self.shuffle.as_ref().map(|x| x.seed),
+            current: self.current,
+            repeat: self.repeat,
+            ..Session::default()
+        }
+    }
+
+    pub fn state(&self) -> QueueState {
+        QueueState {
+            hashes: self.entries.iter().map(|x| x.hash.clone()).collect(),
+            current: self.current,
+            repeat: self.repeat,
+            shuffle_seed: self.shuffle.as_ref().map(|x| x.seed),
+        }
+    }
+}
+
+// puts the entries right after the one with `hash`, or at the start without it,
+// and returns where that one ends up
+fn place_after(list: &mut Vec<QueueEntry>, hash: Option<&str>, entries: &[QueueEntry]) -> usize {
+    list.retain(|x| !entries.iter().any(|entry| entry.hash == x.hash));
+
+    let index = hash.and_then(|hash| list.iter().position(|x| x.hash == hash));
+    let at = index.map_or(0, |x| x + 1);
+
+    list.splice(at..at, entries.iter().cloned());
+    index.unwrap_or(0)
+}
+
+fn random_seed() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.subsec_nanos() ^ x.as_secs() as u32)
+}
+
+// tiny and stable across versions, unlike a general purpose rng whose output
+// for a given seed may change on an update
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(hashes: &str) -> Vec<QueueEntry> {
+        hashes
+            .chars()
+            .map(|x| QueueEntry {
+                hash: x.to_string(),
+                path: PathBuf::from(format!("/music/{x}.flac")),
+            })
+            .collect()
+    }
+
+    fn queue(hashes: &str, current: usize) -> Queue {
+        let mut queue = Queue::default();
+        queue.set(entries(hashes));
+        queue.set_current(current).unwrap();
+        queue
+    }
+
+    fn order(queue: &Queue) -> String {
+        queue.entries().iter().map(|x| x.hash.as_str()).collect()
+    }
+
+    fn current(queue: &Queue) -> &str {
+        &queue.entries()[queue.current()].hash
+    }
+
+    #[test]
+    fn removes_around_the_current_track() {
+        let mut before = queue("abcde", 2);
+        assert_eq!(before.remove(0).unwrap(), Removed::Other);
+        assert_eq!((order(&before).as_str(), current(&before)), ("bcde", "c"));
+
+        let mut at = queue("abcde", 2);
+        assert_eq!(at.remove(2).unwrap(), Removed::Current);
+        assert_eq!((order(&at).as_str(), current(&at)), ("abde", "d"));
+
+        let mut after = queue("abcde", 2);
+        assert_eq!(after.remove(4).unwrap(), Removed::Other);
+        assert_eq!((order(&after).as_str(), current(&after)), ("abcd", "c"));
+
+        assert!(queue("abc", 0).remove(3).is_err());
+    }
+
+    #[test]
+    fn removing_the_last_track_while_current_takes_nothing_in_its_place() {
+        let mut queue = queue("abc", 2);
+
+        assert_eq!(queue.remove(2).unwrap(), Removed::Last);
+        assert_eq!(queue.current(), 1);
+
+        let mut only = Queue::default();
+        only.set(entries("a"));
+
+        assert_eq!(only.remove(0).unwrap(), Removed::Last);
+        assert_eq!(only.current(), 0);
+        assert!(only.entries().is_empty());
+    }
+
+    #[test]
+    fn removes_from_the_order_to_unshuffle_to() {
+        let mut queue = queue("abcde", 0);
+        queue.shuffle(Some(7));
+
+        let index = queue.entries().iter().position(|x| x.hash == "c").unwrap();
+        queue.remove(index).unwrap();
+        queue.unshuffle();
+
+        assert_eq!(order(&queue), "abde");
+        assert_eq!(current(&queue), "a");
+    }
+
+    #[test]
+    fn moves_entries_around_the_current_track() {
+        // the current track itself
+        let mut moved = queue("abcde", 2);
+        moved.move_entry(2, 0).unwrap();
+        assert_eq!((order(&moved).as_str(), moved.current()), ("cabde", 0));
+
+        // from before it to after it
+        let mut forward = queue("abcde", 2);
+        forward.move_entry(0, 4).unwrap();
+        assert_eq!(
+            (order(&forward).as_str(), current(&forward)),
+            ("bcdea", "c")
+        );
+
+        // from after it to before it
+        let mut back = queue("abcde", 2);
+        back.move_entry(4, 0).unwrap();
+        assert_eq!((order(&back).as_str(), current(&back)), ("eabcd", "c"));
+
+        // onto its spot, from either side
+        let mut onto = queue("abcde", 2);
+        onto.move_entry(0, 2).unwrap();
+        assert_eq!((order(&onto).as_str(), current(&onto)), ("bcade", "c"));
+
+        onto.move_entry(4, 1).unwrap();
+        assert_eq!((order(&onto).as_str(), current(&onto)), ("becad", "c"));
+
+        // entirely after it
+        let mut after = queue("abcde", 1);
+        after.move_entry(4, 2).unwrap();
+        assert_eq!((order(&after).as_str(), current(&after)), ("abecd", "b"));
+
+        assert!(queue("abc", 0).move_entry(0, 3).is_err());
+    }
+
+    #[test]
+    fn inserts_next_and_moves_tracks_already_queued() {
+        let mut queue = queue("abcde", 2);
+
+        queue.insert_next(entries("xy"));
+        assert_eq!((order(&queue).as_str(), current(&queue)), ("abcxyde", "c"));
+
+        // one from before the current track and one after it
+        queue.insert_next(entries("ae"));
+        assert_eq!((order(&queue).as_str(), current(&queue)), ("bcaexyd", "c"));
+
+        // the current track stays where it is
+        queue.insert_next(entries("c"));
+        assert_eq!((order(&queue).as_str(), current(&queue)), ("bcaexyd", "c"));
+    }
+
+    #[test]
+    fn inserts_next_into_the_order_to_unshuffle_to() {
+        let mut queue = queue("abcde", 1);
+        queue.shuffle(Some(7));
+        queue.insert_next(entries("xd"));
+
+        assert_eq!(current(&queue), "b");
+        assert_eq!(&order(&queue)[..3], "bxd");
+
+        queue.unshuffle();
+        assert_eq!((order(&queue).as_str(), current(&queue)), ("abxdce", "b"));
+    }
+
+    #[test]
+    fn appends_only_tracks_not_queued_yet() {
+        let mut queue = queue("abc", 1);
+
+        queue.append(entries("cdad"));
+        assert_eq!((order(&queue).as_str(), current(&queue)), ("abcd", "b"));
+
+        queue.shuffle(Some(7));
+        queue.append(entries("ex"));
+
+        assert_eq!(&order(&queue)[4..], "ex");
+
+        queue.unshuffle();
+        assert_eq!((order(&queue).as_str(), current(&queue)), ("abcdex", "b"));
+    }
+
+    #[test]
+    fn shuffles_the_same_way_for_the_same_seed() {
+        let mut first = queue("abcdefgh", 3);
+        let mut second = queue("abcdefgh", 3);
+
+        first.shuffle(Some(42));
+        second.shuffle(Some(42));
+
+        assert_eq!(order(&first), order(&second));
+        assert_eq!((first.current(), current(&first)), (0, "d"));
+
+        let mut sorted: Vec<char> = order(&first).chars().collect();
+        sorted.sort_unstable();
+        assert_eq!(sorted.into_iter().collect::<String>(), "abcdefgh");
+    }
+
+    #[test]
+    fn unshuffles_to_the_original_order_on_the_same_track() {
+        let mut queue = queue("abcdefgh", 3);
+
+        queue.shuffle(Some(42));
+        queue.set_current(5).unwrap();
+        let playing = current(&queue).to_string();
+
+        // shuffling again still goes back to the order from before the first shuffle
+        queue.shuffle(Some(9));
+        queue.unshuffle();
+
+        assert_eq!(order(&queue), "abcdefgh");
+        assert_eq!(current(&queue), playing);
+
+        // nothing to go back to
+        queue.unshuffle();
+        assert_eq!(order(&queue), "abcdefgh");
+    }
+}
@@ -1,5 +1,13 @@
 CREATE TABLE IF NOT EXISTS dirs (                
-    path    TEXT    PRIMARY KEY
+    path            TEXT        PRIMARY KEY,
+    excludes        TEXT        NOT NULL DEFAULT '[]',
+    follow_links    INTEGER     NOT NULL DEFAULT 0,
+    max_depth       INTEGER,
+    skip_hidden     INTEGER     NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS excludes (
+    pattern     TEXT    PRIMARY KEY
 );
 
 CREATE TABLE IF NOT EXISTS tracks (                
@@ -8,13 +16,90 @@ CREATE TABLE IF NOT EXISTS tracks (
     name            TEXT        NOT NULL,
     extension       TEXT        NOT NULL,
     duration        INTEGER     NOT NULL,
+    sample_rate     INTEGER,
+    bit_depth       INTEGER,
+    channels        INTEGER,
+    codec           TEXT,
+    bitrate         INTEGER,
     cover           TEXT,
     title           TEXT,
     artist          TEXT,
     album           TEXT,
     album_artist    TEXT,
     date            TEXT,
-    genre           TEXT
+    genre           TEXT,
+    track_number    INTEGER,
+    track_total     INTEGER,
+    disc_number     INTEGER,
+    disc_total      INTEGER,
+    composer        TEXT,
+    conductor       TEXT,
+    bpm             REAL,
+    comment         TEXT,
+    label           TEXT,
+    isrc            TEXT,
+    musicbrainz_track_id TEXT,
+    musicbrainz_album_id TEXT,
+    musicbrainz_artist_id TEXT,
+    musicbrainz_album_artist_id TEXT,
+    musicbrainz_release_group_id TEXT,
+    sort_title      TEXT,
+    sort_artist     TEXT,
+    sort_album      TEXT,
+    sort_album_artist TEXT,
+    sort_composer   TEXT,
+    compilation     INTEGER     NOT NULL DEFAULT 0,
+    size            INTEGER     NOT NULL DEFAULT 0,
+    mtime           INTEGER     NOT NULL DEFAULT 0,
+    track_gain      REAL,
+    track_peak      REAL,
+    album_gain      REAL,
+    album_peak      REAL,
+    loudness        REAL,
+    true_peak       REAL,
+    analyzed        INTEGER     NOT NULL DEFAULT 0
+);
+
+CREATE TABLE IF NOT EXISTS scan_errors (
+    path        TEXT    PRIMARY KEY,
+    kind        TEXT    NOT NULL,
+    message     TEXT    NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS queue_tracks (
+    position            INTEGER     PRIMARY KEY,
+    track_hash          TEXT        NOT NULL,
+    original_position   INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS player_session (
+    id              INTEGER     PRIMARY KEY CHECK (id = 0),
+    current         INTEGER     NOT NULL,
+    position        INTEGER     NOT NULL,
+    volume          REAL        NOT NULL,
+    repeat          TEXT,
+    shuffle_seed    INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS equalizer_presets (
+    name    TEXT    PRIMARY KEY,
+    bands   TEXT    NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS track_equalizers (
+    track_hash      TEXT    PRIMARY KEY,
+    preset_name     TEXT    NOT NULL,
+
+    FOREIGN KEY (preset_name) REFERENCES equalizer_presets(name)
+        ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS album_equalizers (
+    album           TEXT    PRIMARY KEY,
+    preset_name     TEXT    NOT NULL,
+
+    FOREIGN KEY (preset_name) REFERENCES equalizer_presets(name)
+        ON DELETE CASCADE ON UPDATE CASCADE
 );
 
 CREATE TABLE IF NOT EXISTS playlists (
@@ -69,3 +154,9 @@ CREATE TABLE IF NOT EXISTS ruleset (
     track_hash      TEXT        NOT NULL,
     rules           TEXT        NOT NULL
 );
+
+CREATE TABLE IF NOT EXISTS tag_edits (
+    id          INTEGER     PRIMARY KEY AUTOINCREMENT,
+    summary     TEXT        NOT NULL,
+    changes     TEXT        NOT NULL
+);
@@ -10,3 +10,10 @@ DROP TABLE IF EXISTS emotions;
 DROP TABLE IF EXISTS lyrics;
 DROP TABLE IF EXISTS ruleset;
 DROP TABLE IF EXISTS tracks;
+DROP TABLE IF EXISTS scan_errors;
+DROP TABLE IF EXISTS queue_tracks;
+DROP TABLE IF EXISTS player_session;
+DROP TABLE IF EXISTS track_equalizers;
+DROP TABLE IF EXISTS album_equalizers;
+DROP TABLE IF EXISTS equalizer_presets;
+DROP TABLE IF EXISTS tag_edits;
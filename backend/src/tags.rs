@@ -0,0 +1,771 @@
+use crate::tracks::Track;
+use anyhow::{Context, Result, bail};
+use lofty::config::WriteOptions;
+use lofty::file::FileType;
+use lofty::picture::{Picture, PictureType};
+use lofty::prelude::*;
+use lofty::tag::Tag;
+use serde::{Deserialize, Serialize};
+use serde_with::rust::double_option;
+use serde_with::skip_serializing_none;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// a missing field is left as it is in the file, a `null` (or blank text) removes it
+#[skip_serializing_none]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagUpdate {
+    #[serde(default, with = "double_option")]
+    pub title: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    pub artist: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    pub album: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    pub album_artist: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    pub date: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    pub genre: Option<Option<String>>,
+    #[serde(default, with = "double_option")]
+    pub track_number: Option<Option<u32>>,
+    // an image file embedded as the front cover
+    #[serde(default, with = "double_option")]
+    pub cover: Option<Option<PathBuf>>,
+}
+
+impl TagUpdate {
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn set(&mut self, field: Field, value: Option<String>) -> Result<()> {
+        let value = value.filter(|x| !x.is_empty());
+
+        match field {
+            Field::Title => self.title = Some(value),
+            Field::Artist => self.artist = Some(value),
+            Field::Album => self.album = Some(value),
+            Field::AlbumArtist => self.album_artist = Some(value),
+            Field::Date => self.date = Some(value),
+            Field::Genre => self.genre = Some(value),
+            Field::Track => {
+                let number = value
+                    .map(|x| {
+                        x.parse()
+                            .with_context(|| format!("\"{x}\" isn't a track number"))
+                    })
+                    .transpose()?;
+
+                self.track_number = Some(number);
+            }
+        }
+
+        Ok(())
+    }
+
+    // fields already holding the same value would only be rewritten for nothing
+    fn changes_to(mut self, track: &Track) -> Self {
+        fn same<T: PartialEq>(field: &mut Option<Option<T>>, current: &Option<T>) {
+            if field.as_ref() == Some(current) {
+                *field = None;
+            }
+        }
+
+        same(&mut self.title, &track.title);
+        same(&mut self.artist, &track.artist);
+        same(&mut self.album, &track.album);
+        same(&mut self.album_artist, &track.album_artist);
+        same(&mut self.date, &track.date);
+        same(&mut self.genre, &track.genre);
+        same(&mut self.track_number, &track.track_number);
+
+        self
+    }
+
+    // the fields this one touches, as they are on the track now
+    fn restore(&self, track: &Track) -> Self {
+        fn previous<T: Clone>(field: &Option<Option<T>>, current: &Option<T>) -> Option<Option<T>> {
+            field.as_ref().map(|_| current.clone())
+        }
+
+        Self {
+            title: previous(&self.title, &track.title),
+            artist: previous(&self.artist, &track.artist),
+            album: previous(&self.album, &track.album),
+            album_artist: previous(&self.album_artist, &track.album_artist),
+            date: previous(&self.date, &track.date),
+            genre: previous(&self.genre, &track.genre),
+            track_number: previous(&self.track_number, &track.track_number),
+            cover: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum BatchOperation {
+    // covers are left out, there'd be nothing to undo them with
+    Set { update: TagUpdate },
+    // in the order the tracks were given
+    Renumber { start: u32 },
+    // e.g. "%track% - %artist% - %title%" against the file name without its extension
+    FromFilename { pattern: String },
+    ToFilename { pattern: String },
+}
+
+impl BatchOperation {
+    pub fn summary(&self, count: usize) -> String {
+        match self {
+            Self::Set { .. } => format!("Edited tags of {count} tracks"),
+            Self::Renumber { .. } => format!("Renumbered {count} tracks"),
+            Self::FromFilename { .. } => format!("Filled tags of {count} tracks from file names"),
+            Self::ToFilename { .. } => format!("Renamed {count} files"),
+        }
+    }
+}
+
+// what's going to happen to one file, also kept the other way around to undo it
+#[skip_serializing_none]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagChange {
+    pub hash: String,
+    pub path: PathBuf,
+    pub update: TagUpdate,
+    pub rename: Option<PathBuf>,
+    // why the file is skipped
+    pub error: Option<String>,
+}
+
+impl TagChange {
+    fn new(track: &Track) -> Self {
+        Self {
+            hash: track.hash.clone(),
+            path: track.path.clone(),
+            update: TagUpdate::default(),
+            rename: None,
+            error: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.update.is_empty() && self.rename.is_none() && self.error.is_none()
+    }
+
+    // the change that puts the file back the way the track says it is now
+    pub fn undo(&self, track: &Track) -> Self {
+        Self {
+            hash: track.hash.clone(),
+            path: self.rename.clone().unwrap_or_else(|| self.path.clone()),
+            update: self.update.restore(track),
+            rename: self.rename.as_ref().map(|_| self.path.clone()),
+            error: None,
+        }
+    }
+
+    // tags go first, a rename failing after leaves the file where the row says it is
+    pub fn apply(&self) -> Result<PathBuf> {
+        if let Some(error) = &self.error {
+            bail!("{error}");
+        }
+
+        if !self.update.is_empty() {
+            write_tags(&self.path, &self.update)?;
+        }
+
+        let Some(to) = &self.rename else {
+            return Ok(self.path.clone());
+        };
+
+        if to.exists() {
+            bail!("{} already exists", to.display());
+        }
+
+        fs::rename(&self.path, to)?;
+
+        Ok(to.clone())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TagEditResult {
+    pub applied: usize,
+    pub failed: Vec<TagChange>,
+}
+
+// the dry run, nothing is touched until the same changes are applied
+// NOTE: tracks that would stay the same are left out
+pub fn plan(tracks: &[Track], operation: &BatchOperation) -> Result<Vec<TagChange>> {
+    let mut changes = Vec::with_capacity(tracks.len());
+
+    match operation {
+        BatchOperation::Set { update } => {
+            let update = TagUpdate {
+                cover: None,
+                ..update.clone()
+            };
+
+            for track in tracks {
+                changes.push(TagChange {
+                    update: update.clone().changes_to(track),
+                    ..TagChange::new(track)
+                });
+            }
+        }
+
+        BatchOperation::Renumber { start } => {
+            for (track, number) in tracks.iter().zip(*start..) {
+                let update = TagUpdate {
+                    track_number: Some(Some(number)),
+                    ..TagUpdate::default()
+                };
+
+                changes.push(TagChange {
+                    update: update.changes_to(track),
+                    ..TagChange::new(track)
+                });
+            }
+        }
+
+        BatchOperation::FromFilename { pattern } => {
+            let tokens = parse_pattern(pattern)?;
+
+            if tokens
+                .windows(2)
+                .any(|x| matches!(x, [Token::Field(_), Token::Field(_)]))
+            {
+                bail!("Fields in the pattern need some text between them");
+            }
+
+            for track in tracks {
+                let mut change = TagChange::new(track);
+
+                match match_pattern(&tokens, &track.name) {
+                    Some(values) => {
+                        for (field, value) in values {
+                            if let Err(err) = change.update.set(field, Some(value.to_string())) {
+                                change.error = Some(err.to_string());
+                            }
+                        }
+
+                        change.update = change.update.changes_to(track);
+                    }
+                    None => change.error = Some("The file name doesn't match the pattern".into()),
+                }
+
+                changes.push(change);
+            }
+        }
+
+        BatchOperation::ToFilename { pattern } => {
+            let tokens = parse_pattern(pattern)?;
+            let mut targets = HashSet::new();
+
+            for track in tracks {
+                let mut change = TagChange::new(track);
+
+                match format_pattern(&tokens, track) {
+                    Ok(name) => {
+                        let to = track
+                            .path
+                            .with_file_name(format!("{name}.{}", track.extension));
+
+                        if to != track.path {
+                            if !targets.insert(to.clone()) {
+                                change.error = Some("Another file would get the same name".into());
+                            } else if to.exists() {
+                                change.error =
+                                    Some("A file with the same name already exists".into());
+                            }
+
+                            change.rename = Some(to);
+                        }
+                    }
+                    Err(err) => change.error = Some(err.to_string()),
+                }
+
+                changes.push(change);
+            }
+        }
+    }
+
+    changes.retain(|x| !x.is_empty());
+
+    Ok(changes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Track,
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Date,
+    Genre,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Track => "track number",
+            Self::Title => "title",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::AlbumArtist => "album artist",
+            Self::Date => "date",
+            Self::Genre => "genre",
+        }
+    }
+
+    fn of(self, track: &Track) -> Option<String> {
+        match self {
+            // zero padded, so the files sort in order
+            Self::Track => track.track_number.map(|x| format!("{x:02}")),
+            Self::Title => track.title.clone(),
+            Self::Artist => track.artist.clone(),
+            Self::Album => track.album.clone(),
+            Self::AlbumArtist => track.album_artist.clone(),
+            Self::Date => track.date.clone(),
+            Self::Genre => track.genre.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token<'a> {
+    Text(&'a str),
+    Field(Field),
+}
+
+fn parse_pattern(pattern: &str) -> Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut rest = pattern;
+
+    while let Some(start) = rest.find('%') {
+        if start > 0 {
+            tokens.push(Token::Text(&rest[..start]));
+        }
+
+        let after = &rest[start + 1..];
+        let end = after
+            .find('%')
+            .context("A field in the pattern isn't closed with %")?;
+
+        let field = match after[..end].to_lowercase().as_str() {
+            "track" => Field::Track,
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "albumartist" => Field::AlbumArtist,
+            "date" => Field::Date,
+            "genre" => Field::Genre,
+            name => bail!("%{name}% isn't a known field"),
+        };
+
+        tokens.push(Token::Field(field));
+        rest = &after[end + 1..];
+    }
+
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest));
+    }
+
+    if !tokens.iter().any(|x| matches!(x, Token::Field(_))) {
+        bail!("The pattern has no fields");
+    }
+
+    Ok(tokens)
+}
+
+// a field takes everything up to the first occurrence of the text after it
+fn match_pattern<'a>(tokens: &[Token], mut name: &'a str) -> Option<Vec<(Field, &'a str)>> {
+    let mut values = Vec::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Text(text) => name = name.strip_prefix(text)?,
+            Token::Field(field) => {
+                let end = match tokens.get(index + 1) {
+                    Some(Token::Text(text)) => name.find(text)?,
+                    _ => name.len(),
+                };
+
+                values.push((*field, name[..end].trim()));
+                name = &name[end..];
+            }
+        }
+    }
+
+    name.is_empty().then_some(values)
+}
+
+fn format_pattern(tokens: &[Token], track: &Track) -> Result<String> {
+    let mut name = String::new();
+
+    for token in tokens {
+        match token {
+            Token::Text(text) => name.push_str(text),
+            Token::Field(field) => {
+                let value = field
+                    .of(track)
+                    .filter(|x| !x.trim().is_empty())
+                    .with_context(|| format!("The track has no {}", field.name()))?;
+
+                name.push_str(value.trim());
+            }
+        }
+    }
+
+    // characters no file system takes, and separators that would move the file elsewhere
+    let name: String = name
+        .chars()
+        .map(|x| match x {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            x if x.is_control() => '_',
+            x => x,
+        })
+        .collect();
+
+    let name = name.trim().trim_end_matches('.');
+
+    if name.is_empty() {
+        bail!("The file name would be empty");
+    }
+
+    Ok(name.to_string())
+}
+
+// id3v2 for mp3, vorbis comments for flac and ogg, ilst atoms for m4a
+pub fn write_tags(path: &Path, update: &TagUpdate) -> Result<()> {
+    let mut file = lofty::read_from_path(path)?;
+
+    if !matches!(
+        file.file_type(),
+        FileType::Mpeg | FileType::Flac | FileType::Vorbis | FileType::Opus | FileType::Mp4
+    ) {
+        bail!("Tags can only be written to MP3, FLAC, Ogg and M4A files");
+    }
+
+    // the tag symphonia reads first for each of these, a file without one gets it added
+    let tag_type = file.primary_tag_type();
+
+    if file.tag(tag_type).is_none() {
+        file.insert_tag(Tag::new(tag_type));
+    }
+
+    let tag = file.tag_mut(tag_type).context("missing tag")?;
+
+    set_text(tag, ItemKey::TrackTitle, &update.title);
+    set_text(tag, ItemKey::TrackArtist, &update.artist);
+    set_text(tag, ItemKey::AlbumTitle, &update.album);
+    set_text(tag, ItemKey::AlbumArtist, &update.album_artist);
+    set_text(tag, ItemKey::RecordingDate, &update.date);
+    set_text(tag, ItemKey::Genre, &update.genre);
+
+    match update.track_number {
+        Some(Some(number)) => tag.set_track(number),
+        Some(None) => tag.remove_track(),
+        None => {}
+    }
+
+    match &update.cover {
+        Some(Some(cover)) => {
+            let mut file = fs::File::open(cover).context("could not open the cover")?;
+            let mut picture = Picture::from_reader(&mut file)?;
+            picture.set_pic_type(PictureType::CoverFront);
+
+            tag.remove_picture_type(PictureType::CoverFront);
+            tag.push_picture(picture);
+        }
+        // any picture left would be picked up as the cover instead
+        Some(None) => {
+            let types: Vec<_> = tag.pictures().iter().map(|x| x.pic_type()).collect();
+
+            for pic_type in types {
+                tag.remove_picture_type(pic_type);
+            }
+        }
+        None => {}
+    }
+
+    file.save_to_path(path, WriteOptions::default())?;
+
+    Ok(())
+}
+
+fn set_text(tag: &mut Tag, key: ItemKey, value: &Option<Option<String>>) {
+    let Some(value) = value else {
+        return;
+    };
+
+    match value.as_deref().map(str::trim).filter(|x| !x.is_empty()) {
+        Some(text) => _ = tag.insert_text(key, text.to_string()),
+        None => tag.remove_key(key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::env::temp_dir;
+
+    // copied so the fixture itself is never written to
+    fn fixture(name: &str) -> PathBuf {
+        let from = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        let to = temp_dir().join(format!("meowsic-tags-{name}"));
+
+        fs::copy(from, &to).unwrap();
+        to
+    }
+
+    // one per test, as they run side by side
+    fn cover(name: &str) -> PathBuf {
+        let path = temp_dir().join(format!("meowsic-tags-{name}.png"));
+
+        RgbImage::from_pixel(4, 4, Rgb([255, 128, 0]))
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap();
+
+        path
+    }
+
+    fn read(path: &Path) -> Track {
+        Track::new(path, temp_dir().join("meowsic-tags-covers")).unwrap()
+    }
+
+    fn round_trip(name: &str) {
+        let path = fixture(name);
+        let cover = cover(name);
+        fs::create_dir_all(temp_dir().join("meowsic-tags-covers")).unwrap();
+
+        let update = TagUpdate {
+            title: Some(Some("Title".into())),
+            artist: Some(Some("Artist".into())),
+            album: Some(Some("Album".into())),
+            album_artist: Some(Some("Album Artist".into())),
+            date: Some(Some("2024".into())),
+            genre: Some(Some("Genre".into())),
+            track_number: Some(Some(7)),
+            cover: Some(Some(cover.clone())),
+        };
+
+        write_tags(&path, &update).unwrap();
+        let track = read(&path);
+
+        // the audio is still there after the tags were rewritten
+        assert!(track.duration > 0, "{name}");
+        assert_eq!(track.title.as_deref(), Some("Title"), "{name}");
+        assert_eq!(track.artist.as_deref(), Some("Artist"), "{name}");
+        assert_eq!(track.album.as_deref(), Some("Album"), "{name}");
+        assert_eq!(
+            track.album_artist.as_deref(),
+            Some("Album Artist"),
+            "{name}"
+        );
+        assert_eq!(track.date.as_deref(), Some("2024"), "{name}");
+        assert_eq!(track.genre.as_deref(), Some("Genre"), "{name}");
+        assert_eq!(track.track_number, Some(7), "{name}");
+
+        let saved = track.cover.as_ref().expect(name);
+        assert_eq!(
+            fs::read(saved).unwrap(),
+            fs::read(&cover).unwrap(),
+            "{name}"
+        );
+
+        // fields left out stay as they are
+        write_tags(
+            &path,
+            &TagUpdate {
+                title: Some(Some("Other".into())),
+                ..TagUpdate::default()
+            },
+        )
+        .unwrap();
+
+        let track = read(&path);
+
+        assert_eq!(track.title.as_deref(), Some("Other"), "{name}");
+        assert_eq!(track.artist.as_deref(), Some("Artist"), "{name}");
+        assert_eq!(track.track_number, Some(7), "{name}");
+        assert!(track.cover.is_some(), "{name}");
+
+        let removed = TagUpdate {
+            title: Some(None),
+            artist: Some(None),
+            album: Some(None),
+            album_artist: Some(None),
+            date: Some(None),
+            genre: Some(None),
+            track_number: Some(None),
+            cover: Some(None),
+        };
+
+        write_tags(&path, &removed).unwrap();
+        let track = read(&path);
+
+        assert_eq!(track.title, None, "{name}");
+        assert_eq!(track.artist, None, "{name}");
+        assert_eq!(track.album, None, "{name}");
+        assert_eq!(track.album_artist, None, "{name}");
+        assert_eq!(track.date, None, "{name}");
+        assert_eq!(track.genre, None, "{name}");
+        assert_eq!(track.track_number, None, "{name}");
+        assert_eq!(track.cover, None, "{name}");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn round_trips_mp3() {
+        round_trip("silence.mp3");
+    }
+
+    #[test]
+    fn round_trips_flac() {
+        round_trip("silence.flac");
+    }
+
+    #[test]
+    fn round_trips_ogg_opus() {
+        round_trip("silence.opus");
+    }
+
+    #[test]
+    fn round_trips_m4a() {
+        round_trip("silence.m4a");
+    }
+
+    fn track() -> Track {
+        Track {
+            name: "01 - Artist - Title".into(),
+            track_number: Some(1),
+            artist: Some("Artist".into()),
+            title: Some("Title".into()),
+            ..Track::default()
+        }
+    }
+
+    #[test]
+    fn parses_patterns() {
+        assert_eq!(
+            parse_pattern("%track% - %artist% - %title%").unwrap(),
+            [
+                Token::Field(Field::Track),
+                Token::Text(" - "),
+                Token::Field(Field::Artist),
+                Token::Text(" - "),
+                Token::Field(Field::Title),
+            ]
+        );
+
+        assert_eq!(
+            parse_pattern("(%AlbumArtist%)%date%.").unwrap(),
+            [
+                Token::Text("("),
+                Token::Field(Field::AlbumArtist),
+                Token::Text(")"),
+                Token::Field(Field::Date),
+                Token::Text("."),
+            ]
+        );
+
+        assert!(parse_pattern("%track% - %title").is_err());
+        assert!(parse_pattern("%track% - %name%").is_err());
+        assert!(parse_pattern("no fields").is_err());
+    }
+
+    #[test]
+    fn matches_file_names_against_patterns() {
+        let tokens = parse_pattern("%track% - %artist% - %title%").unwrap();
+
+        assert_eq!(
+            match_pattern(&tokens, "01 - Artist - Title"),
+            Some(vec![
+                (Field::Track, "01"),
+                (Field::Artist, "Artist"),
+                (Field::Title, "Title"),
+            ])
+        );
+
+        // the separator found first ends a field, anything after goes to the last one
+        assert_eq!(
+            match_pattern(&tokens, "01 - Artist - Title - Live"),
+            Some(vec![
+                (Field::Track, "01"),
+                (Field::Artist, "Artist"),
+                (Field::Title, "Title - Live"),
+            ])
+        );
+
+        assert_eq!(match_pattern(&tokens, "01 - Title"), None);
+
+        // text around the fields has to be there as well
+        let tokens = parse_pattern("[%track%] %title%.").unwrap();
+
+        assert_eq!(
+            match_pattern(&tokens, "[ 3 ] Title."),
+            Some(vec![(Field::Track, "3"), (Field::Title, "Title")])
+        );
+
+        assert_eq!(match_pattern(&tokens, "3 Title."), None);
+        assert_eq!(match_pattern(&tokens, "[3] Title"), None);
+    }
+
+    #[test]
+    fn refuses_adjacent_fields_when_reading_file_names() {
+        let operation = BatchOperation::FromFilename {
+            pattern: "%track%%title%".into(),
+        };
+
+        assert!(plan(&[track()], &operation).is_err());
+    }
+
+    #[test]
+    fn formats_file_names_from_patterns() {
+        let tokens = parse_pattern("%track% - %artist% - %title%").unwrap();
+        assert_eq!(
+            format_pattern(&tokens, &track()).unwrap(),
+            "01 - Artist - Title"
+        );
+
+        // nothing to tell them apart by is needed going this way
+        let tokens = parse_pattern("%track%%title%").unwrap();
+        assert_eq!(format_pattern(&tokens, &track()).unwrap(), "01Title");
+
+        let tokens = parse_pattern("%genre% - %title%").unwrap();
+        assert!(format_pattern(&tokens, &track()).is_err());
+    }
+
+    #[test]
+    fn formats_file_names_without_illegal_characters() {
+        let tokens = parse_pattern("%artist% - %title%").unwrap();
+        let track = Track {
+            artist: Some("AC/DC".into()),
+            title: Some("Who? <Me>: \"Live\" | *\\Edit\t...".into()),
+            ..track()
+        };
+
+        assert_eq!(
+            format_pattern(&tokens, &track).unwrap(),
+            "AC_DC - Who_ _Me__ _Live_ _ __Edit_"
+        );
+
+        // all dots and blanks, nothing left to name the file
+        let tokens = parse_pattern("%title%").unwrap();
+        let track = Track {
+            title: Some(" ... ".into()),
+            ..track
+        };
+
+        assert!(format_pattern(&tokens, &track).is_err());
+    }
+}
@@ -0,0 +1,356 @@
+use rodio::Source;
+use rodio::source::SeekError;
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+// length of the pieces the stretched audio is glued together from, long
+// enough to hold a couple of periods of a bass note
+const WINDOW: Duration = Duration::from_millis(40);
+
+// how far from where it should come from a piece may be taken so its waveform
+// lines up with the end of the one before it
+const TOLERANCE: Duration = Duration::from_millis(12);
+
+// lining up waveforms doesn't need every frame compared
+const STRIDE: usize = 4;
+
+pub const MIN_RATE: f32 = 0.25;
+pub const MAX_RATE: f32 = 4.0;
+
+// semitones either way
+pub const MAX_PITCH: f32 = 12.0;
+
+// read by the audio thread on every sample, so changes are heard right away
+#[derive(Debug)]
+pub struct TempoControl {
+    rate: AtomicU32,
+    pitch: AtomicU32,
+}
+
+impl Default for TempoControl {
+    fn default() -> Self {
+        Self {
+            rate: AtomicU32::new(1f32.to_bits()),
+            pitch: AtomicU32::new(0f32.to_bits()),
+        }
+    }
+}
+
+impl TempoControl {
+    pub fn rate(&self) -> f32 {
+        f32::from_bits(self.rate.load(Ordering::Relaxed))
+    }
+
+    pub fn set_rate(&self, rate: f32) {
+        let rate = rate.clamp(MIN_RATE, MAX_RATE);
+        self.rate.store(rate.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn pitch(&self) -> f32 {
+        f32::from_bits(self.pitch.load(Ordering::Relaxed))
+    }
+
+    pub fn set_pitch(&self, semitones: f32) {
+        let semitones = semitones.clamp(-MAX_PITCH, MAX_PITCH);
+        self.pitch.store(semitones.to_bits(), Ordering::Relaxed);
+    }
+
+    // how much longer to stretch the audio and how fast to then read it back,
+    // stretching by the pitch ratio and reading faster by it shifts the pitch
+    // while keeping the length, `None` when playing as is
+    fn ratios(&self) -> Option<(f64, f64)> {
+        let rate = self.rate() as f64;
+        let pitch = 2f64.powf(self.pitch() as f64 / 12.0);
+
+        (rate != 1.0 || pitch != 1.0).then_some((pitch / rate, pitch))
+    }
+}
+
+// changes tempo without touching pitch (and pitch without touching tempo) by
+// overlapping short pieces of the source, each one taken from wherever its
+// waveform best continues the last (WSOLA), then resampling for the pitch
+pub struct Tempo<S> {
+    inner: S,
+    control: Arc<TempoControl>,
+    channels: usize,
+    sample_rate: u32,
+    // all in frames
+    window: usize,
+    hop: usize,
+    tolerance: usize,
+    fade: Vec<f32>,
+    // whether the samples below are in use, plain playback skips all of it
+    active: bool,
+    exhausted: bool,
+    // source samples not needed anymore are dropped from the front as it goes
+    input: Vec<f32>,
+    // where the next piece should come from, into `input`
+    position: f64,
+    // what followed the last piece in the source, the next one should sound like it
+    continuation: Option<usize>,
+    // pieces being overlapped, one window long
+    overlap: Vec<f32>,
+    stretched: VecDeque<f32>,
+    // into `stretched`
+    resample_position: f64,
+    ready: VecDeque<f32>,
+}
+
+impl<S> Tempo<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(inner: S, control: Arc<TempoControl>) -> Self {
+        let channels = inner.channels() as usize;
+        let sample_rate = inner.sample_rate();
+        let frames = |x: Duration| (x.as_secs_f64() * sample_rate as f64) as usize;
+
+        let hop = frames(WINDOW) / 2;
+        let window = hop * 2;
+
+        // a periodic hann window, halves overlapping by a hop always add up to one
+        let fade = (0..window)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / window as f32).cos())
+            .collect();
+
+        Self {
+            inner,
+            control,
+            channels,
+            sample_rate,
+            window,
+            hop,
+            tolerance: frames(TOLERANCE),
+            fade,
+            active: false,
+            exhausted: false,
+            input: vec![],
+            position: 0.0,
+            continuation: None,
+            overlap: vec![0.0; window * channels],
+            stretched: VecDeque::new(),
+            resample_position: 0.0,
+            ready: VecDeque::new(),
+        }
+    }
+
+    fn frames(&self) -> usize {
+        self.input.len() / self.channels
+    }
+
+    fn reset(&mut self) {
+        self.active = false;
+        self.exhausted = false;
+        self.input.clear();
+        self.position = 0.0;
+        self.continuation = None;
+        self.overlap.fill(0.0);
+        self.stretched.clear();
+        self.resample_position = 0.0;
+        self.ready.clear();
+    }
+
+    // goes back to playing the source as is, without losing what was read ahead
+    fn flush(&mut self) {
+        let start = (self.position as usize).min(self.frames()) * self.channels;
+        let rest: Vec<f32> = self.input.drain(start..).collect();
+
+        self.reset();
+        self.ready.extend(rest);
+    }
+
+    fn fill(&mut self, count: usize) -> bool {
+        while self.frames() < count {
+            for _ in 0..self.channels {
+                match self.inner.next() {
+                    Some(sample) => self.input.push(sample),
+                    None => {
+                        self.exhausted = true;
+                        return false;
+                    }
+                }
+            }
+        }
+
+        true
+    }
+
+    // adds one hop of stretched frames, false once the source has run out
+    fn stretch(&mut self, ratio: f64) -> bool {
+        let nominal = self.position.round() as usize;
+        let continuation = self.continuation;
+
+        let needed = (nominal + self.tolerance).max(continuation.unwrap_or(0)) + self.window;
+
+        if !self.fill(needed) {
+            return false;
+        }
+
+        let start = match continuation {
+            Some(continuation) => self.best_match(continuation, nominal),
+            None => nominal,
+        };
+
+        let channels = self.channels;
+
+        for (i, fade) in self.fade.iter().enumerate() {
+            let from = (start + i) * channels;
+
+            for c in 0..channels {
+                self.overlap[i * channels + c] += self.input[from + c] * fade;
+            }
+        }
+
+        let hop = self.hop * channels;
+
+        self.stretched.extend(self.overlap.drain(..hop));
+        self.overlap.resize(self.window * channels, 0.0);
+
+        self.position += self.hop as f64 / ratio;
+
+        // everything before both the next continuation and the next search is done with
+        let done = (start + self.hop).min((self.position as usize).saturating_sub(self.tolerance));
+
+        self.input.drain(..done * channels);
+        self.continuation = Some(start + self.hop - done);
+        self.position -= done as f64;
+
+        true
+    }
+
+    // the start, within the tolerance around `nominal`, whose waveform is most
+    // like the one that naturally follows the last piece
+    fn best_match(&self, continuation: usize, nominal: usize) -> usize {
+        let from = nominal.saturating_sub(self.tolerance);
+        let to = nominal + self.tolerance;
+
+        let score = |start: usize| {
+            let mut dot = 0.0;
+            let mut energy = 0.0;
+
+            for i in (0..self.hop).step_by(STRIDE) {
+                let a = self.mono(continuation + i);
+                let b = self.mono(start + i);
+
+                dot += a * b;
+                energy += b * b;
+            }
+
+            if energy > 0.0 {
+                dot / energy.sqrt()
+            } else {
+                0.0
+            }
+        };
+
+        let best = |starts: &mut dyn Iterator<Item = usize>| {
+            starts
+                .map(|x| (x, score(x)))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|x| x.0)
+        };
+
+        // every other start first, then the neighbours of the best one
+        let coarse = best(&mut (from..=to).step_by(2)).unwrap_or(nominal);
+
+        best(&mut (coarse.saturating_sub(1).max(from)..=(coarse + 1).min(to))).unwrap_or(coarse)
+    }
+
+    fn mono(&self, frame: usize) -> f32 {
+        let from = frame * self.channels;
+        self.input[from..from + self.channels].iter().sum()
+    }
+
+    // reads the stretched frames back `speed` times as fast, interpolating between them
+    fn resample(&mut self, speed: f64) {
+        let channels = self.channels;
+        let frames = self.stretched.len() / channels;
+
+        while (self.resample_position as usize) + 1 < frames {
+            let i = self.resample_position as usize;
+            let t = self.resample_position.fract() as f32;
+
+            for c in 0..channels {
+                let a = self.stretched[i * channels + c];
+                let b = self.stretched[(i + 1) * channels + c];
+
+                self.ready.push_back(a + (b - a) * t);
+            }
+
+            self.resample_position += speed;
+        }
+
+        let used = (self.resample_position as usize).min(frames);
+
+        self.stretched.drain(..used * channels);
+        self.resample_position -= used as f64;
+    }
+}
+
+impl<S> Iterator for Tempo<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if let Some(sample) = self.ready.pop_front() {
+                return Some(sample);
+            }
+
+            let Some((ratio, speed)) = self.control.ratios() else {
+                if self.active {
+                    self.flush();
+                    continue;
+                }
+
+                return self.inner.next();
+            };
+
+            if self.exhausted {
+                self.flush();
+
+                // whatever was read ahead before running out still plays, unstretched
+                return self.ready.pop_front();
+            }
+
+            self.active = true;
+
+            if self.stretch(ratio) {
+                self.resample(speed);
+            }
+        }
+    }
+}
+
+impl<S> Source for Tempo<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.inner.try_seek(pos)?;
+        self.reset();
+
+        Ok(())
+    }
+}
@@ -1,18 +1,28 @@
-use crate::db::TrackRow;
+use crate::covers;
+use crate::db::{DirRow, TrackRow};
 use crate::utils;
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow, bail};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
-use std::fmt::Debug;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use symphonia::core::codecs::CodecParameters;
+use symphonia::core::errors::Error as SymphoniaError;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::{MetadataOptions, StandardTagKey, StandardVisualKey};
 use symphonia::core::probe::Hint;
-use symphonia::default::get_probe;
+use symphonia::default::{get_codecs, get_probe};
 use tauri_plugin_http::reqwest::Client as HttpClient;
-use walkdir::WalkDir;
+use walkdir::{DirEntry, WalkDir};
 
 #[skip_serializing_none]
 #[derive(Debug, Default, Clone, Serialize)]
@@ -22,7 +32,15 @@ pub struct Track {
     pub path: PathBuf,
     pub name: String,
     pub extension: String,
+    // milliseconds
     pub duration: u64,
+    pub sample_rate: Option<u32>,
+    // only for lossless codecs, lossy ones don't have one
+    pub bit_depth: Option<u32>,
+    pub channels: Option<u32>,
+    pub codec: Option<String>,
+    // average kbps, embedded pictures aside
+    pub bitrate: Option<u32>,
     pub cover: Option<PathBuf>,
     pub title: Option<String>,
     pub artist: Option<String>,
@@ -30,6 +48,34 @@ pub struct Track {
     pub album_artist: Option<String>,
     pub date: Option<String>,
     pub genre: Option<String>,
+    pub track_number: Option<u32>,
+    pub track_total: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub disc_total: Option<u32>,
+    pub composer: Option<String>,
+    pub conductor: Option<String>,
+    pub bpm: Option<f32>,
+    pub comment: Option<String>,
+    pub label: Option<String>,
+    pub isrc: Option<String>,
+    // the recording, which is what taggers write as the track id
+    pub musicbrainz_track_id: Option<String>,
+    pub musicbrainz_album_id: Option<String>,
+    pub musicbrainz_artist_id: Option<String>,
+    pub musicbrainz_album_artist_id: Option<String>,
+    pub musicbrainz_release_group_id: Option<String>,
+    pub sort_title: Option<String>,
+    pub sort_artist: Option<String>,
+    pub sort_album: Option<String>,
+    pub sort_album_artist: Option<String>,
+    pub sort_composer: Option<String>,
+    pub compilation: bool,
+    pub size: u64,
+    pub mtime: u64,
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
     pub position: Option<u64>,
     pub rank: Option<u64>,
     pub rules: Option<String>,
@@ -38,13 +84,13 @@ pub struct Track {
 impl Track {
     pub fn new(path: impl Into<PathBuf>, covers_path: impl AsRef<Path>) -> Result<Self> {
         let path: PathBuf = path.into();
-        let file = fs::File::open(&path)?;
+        let file = fs::File::open(&path).context(ScanErrorKind::Io)?;
 
         let extension = path
             .extension()
             .and_then(|x| x.to_str())
             .map(|x| x.to_string())
-            .context("missing file extension")?;
+            .context(ScanErrorKind::MissingExtension)?;
 
         let file_name = path
             .file_name()
@@ -56,127 +102,702 @@ impl Track {
             .trim_end_matches(&format!(".{extension}"))
             .to_string();
 
-        // IDEAL: ideally this could have been a uuid
-        let hash = utils::hash(file_name.as_bytes());
+        let hash = hash_path(&path);
+        let stat = FileStat::from(&file.metadata().context(ScanErrorKind::Io)?);
 
         let mss = MediaSourceStream::new(Box::new(file), Default::default());
         let mut hint = Hint::new();
         hint.with_extension(&extension);
 
-        let mut probed = get_probe().format(
-            &hint,
-            mss,
-            &FormatOptions::default(),
-            &MetadataOptions::default(),
-        )?;
+        let mut probed = get_probe()
+            .format(
+                &hint,
+                mss,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(|err| {
+                let kind = match err {
+                    SymphoniaError::Unsupported(_) => ScanErrorKind::UnsupportedCodec,
+                    SymphoniaError::IoError(_) => ScanErrorKind::Io,
+                    _ => ScanErrorKind::Probe,
+                };
+
+                anyhow::Error::new(err).context(kind)
+            })?;
 
         let mut data = Self {
             path,
             name,
             extension,
             hash: hash.to_string(),
+            size: stat.size,
+            mtime: stat.mtime,
             ..Self::default()
         };
 
-        if let Some(track) = probed.format.default_track() {
-            if let Some((num_frames, sample_rate)) = track
-                .codec_params
-                .n_frames
-                .zip(track.codec_params.sample_rate)
-            {
-                data.duration = num_frames / sample_rate as u64;
-            }
+        let track = probed
+            .format
+            .default_track()
+            .map(|x| (x.id, x.codec_params.clone()));
+
+        if let Some((_, params)) = &track {
+            data.sample_rate = params.sample_rate;
+            data.bit_depth = params.bits_per_sample.or(params.bits_per_coded_sample);
+            data.channels = params.channels.map(|x| x.count() as u32);
+            data.codec = get_codecs()
+                .get_codec(params.codec)
+                .map(|x| x.short_name.to_string());
+            data.duration = params.n_frames.map_or(0, |x| to_millis(params, x));
         }
 
+        let mut picture_bytes = 0;
+
         if let Some(mut meta) = probed
             .metadata
             .get()
             .or_else(|| Some(probed.format.metadata()))
+            && let Some(rev) = meta.skip_to_latest()
         {
-            if let Some(rev) = meta.skip_to_latest() {
-                for tag in rev.tags() {
-                    if let Some(key) = tag.std_key {
-                        use StandardTagKey::*;
-
-                        match key {
-                            TrackTitle => data.title = Some(tag.value.to_string()),
-                            Artist => data.artist = Some(tag.value.to_string()),
-                            Album => data.album = Some(tag.value.to_string()),
-                            AlbumArtist => data.album_artist = Some(tag.value.to_string()),
-                            Date => data.date = Some(tag.value.to_string()),
-                            Genre => data.genre = Some(tag.value.to_string()),
-                            _ => {}
-                        }
+            for tag in rev.tags() {
+                let Some(key) = tag.std_key else {
+                    // symphonia misspells the vorbis key and doesn't map the id3 frame at all
+                    if ["COMPILATION", "TCMP"]
+                        .iter()
+                        .any(|x| tag.key.eq_ignore_ascii_case(x))
+                    {
+                        data.compilation = parse_flag(&tag.value);
                     }
-                }
 
-                let visuals = rev.visuals();
-                let mut priority = [None, None];
-                let mut others = Vec::with_capacity(visuals.len());
+                    continue;
+                };
+
+                use StandardTagKey::*;
+
+                let text = || parse_text(&tag.value);
 
-                for entry in visuals {
-                    match entry.usage {
-                        Some(StandardVisualKey::FrontCover) => priority[0] = Some(entry),
-                        Some(StandardVisualKey::BackCover) => priority[1] = Some(entry),
-                        _ => others.push(entry),
+                match key {
+                    TrackTitle => data.title = Some(tag.value.to_string()),
+                    Artist => data.artist = Some(tag.value.to_string()),
+                    Album => data.album = Some(tag.value.to_string()),
+                    AlbumArtist => data.album_artist = Some(tag.value.to_string()),
+                    Date => data.date = Some(tag.value.to_string()),
+                    Genre => data.genre = Some(tag.value.to_string()),
+                    TrackNumber => {
+                        data.track_number = parse_number(&tag.value);
+                        data.track_total = data.track_total.or(parse_total(&tag.value));
                     }
+                    TrackTotal => data.track_total = parse_number(&tag.value),
+                    DiscNumber => {
+                        data.disc_number = parse_number(&tag.value);
+                        data.disc_total = data.disc_total.or(parse_total(&tag.value));
+                    }
+                    DiscTotal => data.disc_total = parse_number(&tag.value),
+                    Composer => data.composer = text(),
+                    Conductor => data.conductor = text(),
+                    Bpm => data.bpm = parse_bpm(&tag.value),
+                    // the first one is kept, the rest tend to be player data stored as comments
+                    Comment => {
+                        data.comment = data.comment.take().or_else(|| parse_comment(&tag.value))
+                    }
+                    Label => data.label = text(),
+                    IdentIsrc => data.isrc = text(),
+                    MusicBrainzTrackId | MusicBrainzRecordingId => {
+                        data.musicbrainz_track_id = text()
+                    }
+                    MusicBrainzAlbumId => data.musicbrainz_album_id = text(),
+                    MusicBrainzArtistId => data.musicbrainz_artist_id = text(),
+                    MusicBrainzAlbumArtistId => data.musicbrainz_album_artist_id = text(),
+                    MusicBrainzReleaseGroupId => data.musicbrainz_release_group_id = text(),
+                    SortTrackTitle => data.sort_title = text(),
+                    SortArtist => data.sort_artist = text(),
+                    SortAlbum => data.sort_album = text(),
+                    SortAlbumArtist => data.sort_album_artist = text(),
+                    SortComposer => data.sort_composer = text(),
+                    Compilation => data.compilation = parse_flag(&tag.value),
+                    ReplayGainTrackGain => data.track_gain = parse_gain(&tag.value),
+                    ReplayGainTrackPeak => data.track_peak = parse_gain(&tag.value),
+                    ReplayGainAlbumGain => data.album_gain = parse_gain(&tag.value),
+                    ReplayGainAlbumPeak => data.album_peak = parse_gain(&tag.value),
+                    _ => {}
                 }
+            }
 
-                for entry in priority.into_iter().flatten().chain(others) {
-                    if entry.data.is_empty() {
-                        continue;
-                    }
+            let visuals = rev.visuals();
+            picture_bytes = visuals.iter().map(|x| x.data.len() as u64).sum();
+            let mut priority = [None, None];
+            let mut others = Vec::with_capacity(visuals.len());
+
+            for entry in visuals {
+                match entry.usage {
+                    Some(StandardVisualKey::FrontCover) => priority[0] = Some(entry),
+                    Some(StandardVisualKey::BackCover) => priority[1] = Some(entry),
+                    _ => others.push(entry),
+                }
+            }
+
+            for entry in priority.into_iter().flatten().chain(others) {
+                if entry.data.is_empty() {
+                    continue;
+                }
+
+                let path = covers::save(covers_path.as_ref(), &entry.data, &entry.media_type)
+                    .context(ScanErrorKind::CoverWrite)?;
+
+                data.cover = Some(path);
+                break;
+            }
+        }
 
-                    let (_, ext) = entry.media_type.split_once("/").unwrap_or(("image", "jpg"));
-                    let path = covers_path.as_ref().join(format!("{hash}.{ext}"));
+        // some files don't say how long they are (VBR MP3s without a Xing header, some
+        // AAC and Opus), so it comes from where the last packet ends instead
+        if data.duration == 0
+            && let Some((id, params)) = &track
+        {
+            let mut end = 0;
 
-                    fs::write(&path, &entry.data)?;
-                    data.cover = Some(path);
-                    break;
+            while let Ok(packet) = probed.format.next_packet() {
+                if packet.track_id() == *id {
+                    end = end.max(packet.ts + packet.dur);
                 }
             }
+
+            data.duration = to_millis(params, end);
         }
 
+        // bits per millisecond are kilobits per second
+        data.bitrate = (data.size.saturating_sub(picture_bytes) * 8)
+            .checked_div(data.duration)
+            .and_then(|x| u32::try_from(x).ok());
+
         Ok(data)
     }
 }
 
-pub fn scan(
-    dirs: &[impl AsRef<Path>],
-    covers_path: impl AsRef<Path>,
-) -> Result<(Vec<Track>, Vec<String>)> {
+// timestamps count in the track's time base, or in frames when it doesn't have one
+fn to_millis(params: &CodecParameters, ts: u64) -> u64 {
+    if let Some(time_base) = params.time_base {
+        let time = time_base.calc_time(ts);
+        time.seconds * 1000 + (time.frac * 1000.0) as u64
+    } else if let Some(sample_rate) = params.sample_rate {
+        ts * 1000 / sample_rate as u64
+    } else {
+        0
+    }
+}
+
+// track numbers are often written along with the total, e.g. "3/12"
+fn parse_number(value: &impl ToString) -> Option<u32> {
+    let value = value.to_string();
+    let (number, _) = value.split_once('/').unwrap_or((&value, ""));
+
+    number.trim().parse().ok()
+}
+
+// the "12" of "3/12"
+fn parse_total(value: &impl ToString) -> Option<u32> {
+    let value = value.to_string();
+    let (_, total) = value.split_once('/')?;
+
+    total.trim().parse().ok()
+}
+
+fn parse_text(value: &impl ToString) -> Option<String> {
+    let value = value.to_string();
+    let value = value.trim();
+
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+// usually a whole number, but some taggers write fractions
+fn parse_bpm(value: &impl ToString) -> Option<f32> {
+    let value = value.to_string();
+
+    value
+        .trim()
+        .parse::<f32>()
+        .ok()
+        .filter(|x| x.is_finite() && *x > 0.0)
+}
+
+// iTunes stores its normalization and gapless info as comments made of hex
+// groups, e.g. " 00000A2B 00000B1C ...", which aren't meant to be read
+fn parse_comment(value: &impl ToString) -> Option<String> {
+    let value = parse_text(value)?;
+
+    let is_data = value
+        .split_whitespace()
+        .all(|x| x.len() == 8 && x.chars().all(|c| c.is_ascii_hexdigit()));
+
+    (!is_data).then_some(value)
+}
+
+fn parse_flag(value: &impl ToString) -> bool {
+    let value = value.to_string();
+
+    matches!(
+        value.trim().to_ascii_lowercase().as_str(),
+        "1" | "true" | "yes"
+    )
+}
+
+// ReplayGain values are written as "-6.54 dB" for gains and a bare "0.988" for peaks
+fn parse_gain(value: &impl ToString) -> Option<f32> {
+    let value = value.to_string().replace('\u{2212}', "-");
+    let value = value.trim();
+    let value = value
+        .strip_suffix("dB")
+        .or_else(|| value.strip_suffix("db"))
+        .or_else(|| value.strip_suffix("DB"))
+        .unwrap_or(value);
+
+    value.trim().parse::<f32>().ok().filter(|x| x.is_finite())
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl From<&Track> for ReplayGain {
+    fn from(track: &Track) -> Self {
+        Self {
+            track_gain: track.track_gain,
+            track_peak: track.track_peak,
+            album_gain: track.album_gain,
+            album_peak: track.album_peak,
+        }
+    }
+}
+
+// what the player needs to know about a queued track besides its path
+#[derive(Debug, Default, Clone)]
+pub struct PlaybackInfo {
+    pub gain: ReplayGain,
+    // milliseconds, 0 when unknown
+    pub duration: u64,
+    pub album: Option<String>,
+    pub track_number: Option<u32>,
+    // name of the preset set for the track, or else for its album
+    pub equalizer: Option<String>,
+}
+
+impl From<&Track> for PlaybackInfo {
+    fn from(track: &Track) -> Self {
+        Self {
+            gain: ReplayGain::from(track),
+            duration: track.duration,
+            album: track.album.clone(),
+            track_number: track.track_number,
+            equalizer: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    pub mtime: u64,
+}
+
+impl From<&fs::Metadata> for FileStat {
+    fn from(meta: &fs::Metadata) -> Self {
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|x| x.duration_since(UNIX_EPOCH).ok())
+            .map(|x| x.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            size: meta.len(),
+            mtime,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Scan {
+    // new or modified files, freshly probed
+    pub tracks: Vec<Track>,
+    // every file found, whether probed, skipped as unchanged or failing, anything else is gone
+    pub seen: HashSet<PathBuf>,
+    pub errors: Vec<ScanError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, sqlx::Type)]
+#[serde(rename_all = "camelCase")]
+#[sqlx(rename_all = "snake_case")]
+pub enum ScanErrorKind {
+    UnsupportedCodec,
+    Io,
+    MissingExtension,
+    Probe,
+    CoverWrite,
+}
+
+impl Display for ScanErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnsupportedCodec => "unsupported codec",
+            Self::Io => "could not read file",
+            Self::MissingExtension => "missing file extension",
+            Self::Probe => "could not read audio metadata",
+            Self::CoverWrite => "could not save cover",
+        })
+    }
+}
+
+#[derive(sqlx::FromRow, Debug, Clone, Serialize)]
+pub struct ScanError {
+    pub kind: ScanErrorKind,
+    pub path: String,
+    pub message: String,
+}
+
+impl ScanError {
+    // Track::new tags its failures with a ScanErrorKind context, anything else counts as a probe failure
+    pub fn new(path: &Path, err: &anyhow::Error) -> Self {
+        Self {
+            kind: err
+                .downcast_ref::<ScanErrorKind>()
+                .copied()
+                .unwrap_or(ScanErrorKind::Probe),
+            path: path.to_string_lossy().to_string(),
+            message: err.root_cause().to_string(),
+        }
+    }
+}
+
+pub fn is_supported(path: &Path) -> bool {
     const SUPPORTED: &[&str] = &["mp3", "m4a", "flac", "wav", "ogg", "opus", "aac", "aiff"];
 
-    let mut tracks = Vec::new();
-    let mut errors = Vec::new();
+    SUPPORTED.iter().any(|&x| {
+        path.extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|ext| x.eq_ignore_ascii_case(ext))
+    })
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanProgress {
+    pub discovered: usize,
+    pub processed: usize,
+    pub errors: usize,
+    pub current: Option<PathBuf>,
+}
+
+// throttles progress reports so a large library doesn't flood the UI with events
+struct ProgressReporter<F> {
+    state: Mutex<(ScanProgress, Instant)>,
+    on_progress: F,
+}
+
+impl<F: Fn(&ScanProgress)> ProgressReporter<F> {
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn new(on_progress: F) -> Self {
+        Self {
+            state: Mutex::new((ScanProgress::default(), Instant::now())),
+            on_progress,
+        }
+    }
+
+    fn update(&self, force: bool, f: impl FnOnce(&mut ScanProgress)) {
+        let mut state = self.state.lock();
+        let (progress, reported_at) = &mut *state;
+
+        f(progress);
+
+        if force || reported_at.elapsed() >= Self::INTERVAL {
+            *reported_at = Instant::now();
+            (self.on_progress)(progress);
+        }
+    }
+}
+
+pub fn scan(
+    dirs: &[ScanDir],
+    covers_path: impl AsRef<Path> + Sync,
+    known: &HashMap<PathBuf, FileStat>,
+    cancel: &AtomicBool,
+    on_progress: impl Fn(&ScanProgress) + Sync,
+) -> Result<Scan> {
+    let mut scan = Scan::default();
+    let reporter = ProgressReporter::new(on_progress);
+    let mut walked = HashSet::new();
+    let mut pending = Vec::new();
 
     for dir in dirs {
-        for entry in WalkDir::new(dir)
-            .into_iter()
-            .filter_map(|x| x.ok())
-            .filter(|x| x.file_type().is_file())
-        {
+        for entry in dir.walk() {
+            if cancel.load(Ordering::Relaxed) {
+                bail!("Scan cancelled");
+            }
+
             let path = entry.path();
 
-            if !SUPPORTED.iter().any(|&x| {
-                path.extension()
-                    .and_then(|x| x.to_str())
-                    .is_some_and(|ext| x.eq_ignore_ascii_case(ext))
-            }) {
+            if !is_supported(path) {
+                continue;
+            }
+
+            // overlapping library folders walk the same files more than once
+            if !walked.insert(path.to_path_buf()) {
+                continue;
+            }
+
+            // skip probing files whose size and mtime haven't changed since the last scan
+            if let Ok(meta) = entry.metadata()
+                && known.get(path) == Some(&FileStat::from(&meta))
+            {
+                scan.seen.insert(path.to_path_buf());
+                reporter.update(false, |x| {
+                    x.discovered += 1;
+                    x.processed += 1;
+                });
+
                 continue;
             }
 
-            match Track::new(path, &covers_path) {
-                Ok(track) => tracks.push(track),
-                // simple error format to show in the UI
-                Err(err) => errors.push(format!("[ERR] {} : {err}", path.display())),
+            pending.push(path.to_path_buf());
+            reporter.update(false, |x| x.discovered += 1);
+        }
+    }
+
+    let results = parallel_map(&pending, SCAN_WORKERS, |path| {
+        // drains the remaining files without touching them once cancelled
+        if cancel.load(Ordering::Relaxed) {
+            bail!("Scan cancelled");
+        }
+
+        let result = Track::new(path, &covers_path);
+
+        reporter.update(false, |x| {
+            x.processed += 1;
+            x.errors += result.is_err() as usize;
+            x.current = Some(path.clone());
+        });
+
+        result
+    })?;
+
+    if cancel.load(Ordering::Relaxed) {
+        bail!("Scan cancelled");
+    }
+
+    reporter.update(true, |x| x.current = None);
+
+    for (path, result) in pending.iter().zip(results) {
+        match result {
+            Ok(track) => {
+                scan.seen.insert(track.path.clone());
+                scan.tracks.push(track);
+            }
+            Err(err) => {
+                // a file that can't be read right now is still there, its row stays as it is
+                scan.seen.insert(path.clone());
+                scan.errors.push(ScanError::new(path, &err));
             }
         }
     }
 
+    Ok(scan)
+}
+
+// probes specific files, e.g. to retry the ones that failed during the last scan
+pub fn probe(
+    paths: &[PathBuf],
+    covers_path: impl AsRef<Path> + Sync,
+) -> Result<(Vec<Track>, Vec<ScanError>)> {
+    let results = parallel_map(paths, SCAN_WORKERS, |path| Track::new(path, &covers_path))?;
+
+    let mut tracks = Vec::new();
+    let mut errors = Vec::new();
+
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(track) => tracks.push(track),
+            Err(err) => errors.push(ScanError::new(path, &err)),
+        }
+    }
+
     Ok((tracks, errors))
 }
 
+// caps how many files probing holds open at once
+const SCAN_WORKERS: usize = 8;
+
+// runs `f` over `items` on a few worker threads, each handling one item at a time
+// so no more than `max_workers` items are in flight, results keep the input order
+pub fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    max_workers: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Result<Vec<R>> {
+    let workers = thread::available_parallelism()
+        .map_or(1, |x| x.get())
+        .min(max_workers)
+        .min(items.len())
+        .max(1);
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<R>> = items.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else { break };
+
+                        done.push((index, f(item)));
+                    }
+
+                    done
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            let done = handle.join().map_err(|_| anyhow!("worker panicked"))?;
+
+            for (index, result) in done {
+                results[index] = Some(result);
+            }
+        }
+
+        Ok::<_, anyhow::Error>(())
+    })?;
+
+    Ok(results.into_iter().flatten().collect())
+}
+
+// tracks are identified by their full path, so no two files ever share one and adding or
+// removing library folders leaves it alone, backups match tracks by `relative_path` instead
+pub fn hash_path(path: &Path) -> String {
+    utils::hash(path.to_string_lossy().as_bytes())
+}
+
+// joined with '/' so it's the same on every platform,
+// tracks outside of the library fall back to just the file name
+pub fn relative_path(path: &Path, root: Option<&Path>) -> String {
+    let relative = root
+        .and_then(|x| path.strip_prefix(x).ok())
+        .or_else(|| path.file_name().map(Path::new))
+        .unwrap_or(path);
+
+    relative
+        .iter()
+        .map(|x| x.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// the outermost library folder so nested folders don't change a track's identity
+pub fn library_root<'a>(path: &Path, dirs: &'a [impl AsRef<Path>]) -> Option<&'a Path> {
+    dirs.iter()
+        .map(|x| x.as_ref())
+        .filter(|&x| path.starts_with(x))
+        .min_by_key(|x| x.components().count())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Dir {
+    pub path: String,
+    pub excludes: Vec<String>,
+    pub follow_links: bool,
+    pub max_depth: Option<u32>,
+    pub skip_hidden: bool,
+}
+
+impl From<DirRow> for Dir {
+    fn from(row: DirRow) -> Self {
+        Self {
+            path: row.path,
+            excludes: serde_json::from_str(&row.excludes).unwrap_or_default(),
+            follow_links: row.follow_links,
+            max_depth: row.max_depth.and_then(|x| x.try_into().ok()),
+            skip_hidden: row.skip_hidden,
+        }
+    }
+}
+
+// a library folder ready to be walked, with its own and the global exclude patterns compiled
+#[derive(Debug, Clone)]
+pub struct ScanDir {
+    pub path: PathBuf,
+    follow_links: bool,
+    max_depth: Option<usize>,
+    skip_hidden: bool,
+    excludes: GlobSet,
+}
+
+impl ScanDir {
+    pub fn new(dir: &Dir, global_excludes: &[String]) -> Result<Self> {
+        let mut excludes = GlobSetBuilder::new();
+
+        for pattern in dir.excludes.iter().chain(global_excludes) {
+            excludes.add(
+                Glob::new(pattern).with_context(|| format!("invalid exclude pattern {pattern}"))?,
+            );
+        }
+
+        Ok(Self {
+            path: PathBuf::from(&dir.path),
+            follow_links: dir.follow_links,
+            max_depth: dir.max_depth.map(|x| x as usize),
+            skip_hidden: dir.skip_hidden,
+            excludes: excludes.build()?,
+        })
+    }
+
+    // patterns are matched against the path relative to the folder, so `*.tmp.flac`
+    // matches at any depth and `**/Samples/**` skips every Samples folder
+    pub fn includes(&self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.path) else {
+            return false;
+        };
+
+        self.max_depth
+            .is_none_or(|x| relative.components().count() <= x)
+            && !(self.skip_hidden && relative.iter().any(is_hidden))
+            && !self.excludes.is_match(relative)
+    }
+
+    fn walk(&self) -> impl Iterator<Item = DirEntry> + '_ {
+        let mut walk = WalkDir::new(&self.path)
+            .follow_links(self.follow_links)
+            .sort_by_file_name();
+
+        if let Some(depth) = self.max_depth {
+            walk = walk.max_depth(depth);
+        }
+
+        walk.into_iter()
+            // don't descend into hidden folders at all
+            .filter_entry(|x| !(self.skip_hidden && x.depth() > 0 && is_hidden(x.file_name())))
+            .filter_map(|x| x.ok())
+            .filter(|x| x.file_type().is_file() && self.includes(x.path()))
+    }
+}
+
+impl AsRef<Path> for ScanDir {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn is_hidden(name: &OsStr) -> bool {
+    name.to_string_lossy().starts_with('.')
+}
+
 impl From<TrackRow> for Track {
     fn from(row: TrackRow) -> Self {
         Self {
@@ -185,6 +806,11 @@ impl From<TrackRow> for Track {
             name: row.name,
             extension: row.extension,
             duration: row.duration.try_into().unwrap_or_default(),
+            sample_rate: row.sample_rate,
+            bit_depth: row.bit_depth,
+            channels: row.channels,
+            codec: row.codec,
+            bitrate: row.bitrate,
             cover: row.cover.map(PathBuf::from),
             title: row.title,
             artist: row.artist,
@@ -192,6 +818,33 @@ impl From<TrackRow> for Track {
             album_artist: row.album_artist,
             date: row.date,
             genre: row.genre,
+            track_number: row.track_number,
+            track_total: row.track_total,
+            disc_number: row.disc_number,
+            disc_total: row.disc_total,
+            composer: row.composer,
+            conductor: row.conductor,
+            bpm: row.bpm,
+            comment: row.comment,
+            label: row.label,
+            isrc: row.isrc,
+            musicbrainz_track_id: row.musicbrainz_track_id,
+            musicbrainz_album_id: row.musicbrainz_album_id,
+            musicbrainz_artist_id: row.musicbrainz_artist_id,
+            musicbrainz_album_artist_id: row.musicbrainz_album_artist_id,
+            musicbrainz_release_group_id: row.musicbrainz_release_group_id,
+            sort_title: row.sort_title,
+            sort_artist: row.sort_artist,
+            sort_album: row.sort_album,
+            sort_album_artist: row.sort_album_artist,
+            sort_composer: row.sort_composer,
+            compilation: row.compilation,
+            size: row.size.try_into().unwrap_or_default(),
+            mtime: row.mtime.try_into().unwrap_or_default(),
+            track_gain: row.track_gain,
+            track_peak: row.track_peak,
+            album_gain: row.album_gain,
+            album_peak: row.album_peak,
             rules: row.rules,
             position: row.position.and_then(|x| x.try_into().ok()),
             rank: row.rank.and_then(|x| x.try_into().ok()),
@@ -0,0 +1,198 @@
+use crate::AppState;
+use crate::db::LibraryChanges;
+use crate::tracks::{self, ScanDir, Track};
+use notify_debouncer_full::notify::event::{ModifyKind, RenameMode};
+use notify_debouncer_full::notify::{EventKind, RecommendedWatcher, RecursiveMode};
+use notify_debouncer_full::{DebounceEventResult, DebouncedEvent, Debouncer, RecommendedCache};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use walkdir::WalkDir;
+
+// bursts of events (copying an album, a tagger saving a folder) settle into one batch
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+// reported through `library-watch-error` as it happens and kept for the settings to show
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchError {
+    // the folder that can't be watched, `None` when it's the watcher as a whole
+    pub path: Option<String>,
+    pub message: String,
+}
+
+#[derive(Default)]
+pub struct LibraryWatcher {
+    debouncer: Option<Debouncer<RecommendedWatcher, RecommendedCache>>,
+    errors: Vec<WatchError>,
+}
+
+impl LibraryWatcher {
+    // replaces whatever was being watched before
+    // NOTE: a folder that can't be watched (an unplugged drive, a deleted folder) is skipped and
+    // the rest are still kept in sync, it's tried again whenever the folders are set up next
+    pub fn watch(&mut self, app: &AppHandle, dirs: Vec<ScanDir>) {
+        self.debouncer = None;
+        self.errors.clear();
+
+        if dirs.is_empty() {
+            return;
+        }
+
+        self.debouncer = self.start(app, &dirs);
+
+        if !self.errors.is_empty() {
+            _ = app.emit("library-watch-error", &self.errors);
+        }
+    }
+
+    // from the last time the folders were set up
+    pub fn errors(&self) -> Vec<WatchError> {
+        self.errors.clone()
+    }
+
+    fn start(
+        &mut self,
+        app: &AppHandle,
+        dirs: &[ScanDir],
+    ) -> Option<Debouncer<RecommendedWatcher, RecommendedCache>> {
+        let app = app.clone();
+        let watched = dirs.to_vec();
+
+        let debouncer = notify_debouncer_full::new_debouncer(DEBOUNCE, None, move |result| {
+            let state = app.state::<AppState>();
+            let changes = collect_changes(&watched, &state.db.covers_path, result);
+
+            if changes.is_empty() {
+                return;
+            }
+
+            match tauri::async_runtime::block_on(state.db.sync_changes(&changes)) {
+                Ok(()) => {
+                    _ = app.emit("library-changed", ());
+                    state.analyzer.request(&app);
+                }
+                Err(err) => {
+                    let error = WatchError {
+                        path: None,
+                        message: format!("Couldn't update the library: {err}"),
+                    };
+
+                    _ = app.emit("library-watch-error", [error]);
+                }
+            }
+        });
+
+        let mut debouncer = match debouncer {
+            Ok(debouncer) => debouncer,
+            Err(err) => {
+                self.errors.push(WatchError {
+                    path: None,
+                    message: err.to_string(),
+                });
+
+                return None;
+            }
+        };
+
+        for dir in dirs {
+            if let Err(err) = debouncer.watch(dir, RecursiveMode::Recursive) {
+                self.errors.push(WatchError {
+                    path: Some(dir.as_ref().to_string_lossy().to_string()),
+                    message: err.to_string(),
+                });
+            }
+        }
+
+        Some(debouncer)
+    }
+}
+
+fn collect_changes(
+    dirs: &[ScanDir],
+    covers_path: &Path,
+    result: DebounceEventResult,
+) -> LibraryChanges {
+    let mut changes = LibraryChanges::default();
+
+    let Ok(events) = result else {
+        return changes;
+    };
+
+    // honours the same exclude patterns and options as a full scan
+    let in_library = |path: &Path| dirs.iter().any(|x| x.includes(path));
+
+    // files or whole folders that showed up and need to be walked
+    let mut appeared = BTreeSet::new();
+    // files whose contents changed, folders are ignored here since
+    // their own mtime changes whenever something inside them does
+    let mut modified = BTreeSet::new();
+    let mut removed = BTreeSet::new();
+
+    for DebouncedEvent { event, .. } in events {
+        let paths = event.paths;
+
+        match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if paths.len() == 2 => {
+                let (from, to) = (&paths[0], &paths[1]);
+
+                match (in_library(from), in_library(to)) {
+                    (true, true) => changes.renamed.push((from.clone(), to.clone())),
+                    (true, false) => _ = removed.insert(from.clone()),
+                    _ => {}
+                }
+
+                appeared.insert(to.clone());
+            }
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+                appeared.extend(paths)
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+                removed.extend(paths)
+            }
+            EventKind::Modify(_) | EventKind::Any | EventKind::Other => modified.extend(paths),
+            EventKind::Access(_) => {}
+        }
+    }
+
+    let mut files = BTreeSet::new();
+
+    for path in appeared {
+        if path.is_dir() {
+            files.extend(
+                WalkDir::new(&path)
+                    .into_iter()
+                    .filter_map(|x| x.ok())
+                    .filter(|x| x.file_type().is_file())
+                    .map(|x| x.into_path()),
+            );
+        } else {
+            files.insert(path);
+        }
+    }
+
+    files.extend(modified.into_iter().filter(|x| !x.is_dir()));
+
+    for path in files {
+        if !in_library(&path) || !tracks::is_supported(&path) {
+            continue;
+        }
+
+        // a file that's gone by the time the burst settles was only passing through
+        if !path.exists() {
+            removed.insert(path);
+            continue;
+        }
+
+        removed.remove(&path);
+
+        if let Ok(track) = Track::new(&path, covers_path) {
+            changes.tracks.push(track);
+        }
+    }
+
+    changes.removed = removed.into_iter().filter(|x| in_library(x)).collect();
+    changes
+}
@@ -106,6 +106,7 @@ export const ListItem = memo(
               <Cover
                 className="size-full rounded-none"
                 url={item.cover}
+                size={256}
                 placeholder={Disc3Icon}
                 onClick={() => onOpen(item)}
               />
@@ -84,6 +84,7 @@ export function Window() {
     elapsed: player.elapsed,
     seek: player.seek,
     setVolume: player.setVolume,
+    setRate: player.setRate,
   })
 
   return (
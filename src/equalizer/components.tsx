@@ -0,0 +1,424 @@
+import { useEffect, useState } from 'react'
+import { useMutation, useQuery } from '@tanstack/react-query'
+import { useStore } from 'zustand'
+import {
+  addToast,
+  Button,
+  Input,
+  Modal,
+  ModalBody,
+  ModalContent,
+  ModalFooter,
+  ModalHeader,
+  Popover,
+  PopoverContent,
+  PopoverTrigger,
+  Select,
+  SelectItem,
+  Slider,
+  useDisclosure,
+} from '@heroui/react'
+import { CheckIcon, PencilLineIcon, PlusIcon, RotateCcwIcon, Trash2Icon } from 'lucide-react'
+import { store } from '@/settings'
+import {
+  BAND_KINDS,
+  createBands,
+  getAssignment,
+  getPresets,
+  removePreset,
+  renamePreset,
+  setAlbumPreset,
+  setPreset,
+  setTrackPreset,
+} from '@/equalizer'
+import type { Band, BandKind, EqualizerPreset } from '@/equalizer'
+import type { Track } from '@/tracks'
+
+// preset names are free-form, so this can't clash with one
+const NO_PRESET = '\0none'
+
+const SELECT_CLASS_NAMES = {
+  base: 'w-64 mb-2',
+  trigger: 'dark:bg-default/30 dark:hover:bg-default/40',
+  listbox: 'px-0',
+}
+
+export function EqualizerSettings() {
+  const state = useStore(store)
+  const [editing, setEditing] = useState<string | null>(null)
+  const nameModal = useDisclosure()
+  const [nameModalType, setNameModalType] = useState<'new' | 'rename'>('new')
+
+  const query = useQuery({ queryKey: ['equalizer-presets'], queryFn: getPresets })
+  const preset = query.data?.find(it => it.name === editing) ?? null
+
+  // case: the preset being edited was removed or renamed
+  useEffect(() => {
+    if (!query.data) return
+    if (!editing || !preset) setEditing(query.data[0]?.name ?? null)
+  }, [query.data])
+
+  const mutationSave = useMutation({
+    mutationFn: setPreset,
+    onError: err => addToast({ timeout: 5000, color: 'danger', title: err.message }),
+    onSettled: () => query.refetch(),
+  })
+
+  const mutationRemove = useMutation({
+    mutationFn: async (name: string) => {
+      await removePreset(name)
+      if (store.getState().equalizer === name) store.setState({ equalizer: null })
+    },
+    onError: err => addToast({ timeout: 5000, color: 'danger', title: err.message }),
+    onSuccess: () => {
+      setEditing(null)
+      query.refetch()
+    },
+  })
+
+  const onName = async (name: string) => {
+    if (nameModalType === 'new') {
+      await setPreset({ name, bands: createBands() })
+    } else if (preset) {
+      await renamePreset(preset.name, name)
+      if (store.getState().equalizer === preset.name) store.setState({ equalizer: name })
+    }
+
+    setEditing(name)
+    await query.refetch()
+    nameModal.onClose()
+  }
+
+  return (
+    <>
+      <Select
+        label="Default Equalizer"
+        radius="sm"
+        labelPlacement="outside"
+        popoverProps={{ classNames: { content: 'rounded-small' } }}
+        classNames={SELECT_CLASS_NAMES}
+        selectedKeys={[state.equalizer ?? NO_PRESET]}
+        onSelectionChange={value => {
+          const key = value.currentKey
+          if (!key) return
+
+          store.setState({ equalizer: key === NO_PRESET ? null : key })
+        }}>
+        {[
+          <SelectItem key={NO_PRESET}>Off</SelectItem>,
+          ...(query.data ?? []).map(it => <SelectItem key={it.name}>{it.name}</SelectItem>),
+        ]}
+      </Select>
+
+      <div className="text-small text-default-500">
+        Tracks and albums can have a preset of their own, set from their details.
+      </div>
+
+      <div className="flex items-end gap-2">
+        <Select
+          label="Preset"
+          radius="sm"
+          labelPlacement="outside"
+          placeholder="No presets yet"
+          popoverProps={{ classNames: { content: 'rounded-small' } }}
+          classNames={{ ...SELECT_CLASS_NAMES, base: 'w-64' }}
+          isDisabled={!query.data?.length}
+          selectedKeys={editing ? [editing] : []}
+          onSelectionChange={value => {
+            const key = value.currentKey
+            if (key) setEditing(key)
+          }}>
+          {(query.data ?? []).map(it => (
+            <SelectItem key={it.name}>{it.name}</SelectItem>
+          ))}
+        </Select>
+
+        <Button
+          isIconOnly
+          radius="sm"
+          variant="flat"
+          onPress={() => {
+            setNameModalType('new')
+            nameModal.onOpen()
+          }}>
+          <PlusIcon className="text-lg" />
+        </Button>
+
+        <Button
+          isIconOnly
+          radius="sm"
+          variant="flat"
+          isDisabled={!preset}
+          onPress={() => {
+            setNameModalType('rename')
+            nameModal.onOpen()
+          }}>
+          <PencilLineIcon className="text-lg" />
+        </Button>
+
+        <Button
+          isIconOnly
+          radius="sm"
+          variant="flat"
+          isDisabled={!preset}
+          onPress={() => {
+            if (preset) mutationSave.mutate({ ...preset, bands: preset.bands.map(it => ({ ...it, gain: 0 })) })
+          }}>
+          <RotateCcwIcon className="text-lg" />
+        </Button>
+
+        <Button
+          isIconOnly
+          radius="sm"
+          variant="flat"
+          color="danger"
+          isDisabled={!preset}
+          isLoading={mutationRemove.isPending}
+          onPress={() => preset && mutationRemove.mutate(preset.name)}>
+          <Trash2Icon className="text-lg" />
+        </Button>
+      </div>
+
+      {preset && <BandsEditor key={preset.name} preset={preset} onChange={mutationSave.mutate} />}
+
+      <PresetNameModal
+        key={`${nameModalType}-${nameModal.isOpen}`}
+        isOpen={nameModal.isOpen}
+        onOpenChange={nameModal.onOpenChange}
+        existing={nameModalType === 'rename' ? preset?.name : undefined}
+        onAction={onName}
+      />
+    </>
+  )
+}
+
+type BandsEditorProps = { preset: EqualizerPreset; onChange: (preset: EqualizerPreset) => void }
+
+// saves as soon as a band is let go of, so it can be heard on whatever is playing
+function BandsEditor({ preset, onChange }: BandsEditorProps) {
+  const [bands, setBands] = useState(preset.bands)
+
+  useEffect(() => setBands(preset.bands), [preset.bands])
+
+  const update = (index: number, band: Partial<Band>, save = true) => {
+    const next = bands.map((it, i) => (i === index ? { ...it, ...band } : it))
+
+    setBands(next)
+    if (save) onChange({ ...preset, bands: next })
+  }
+
+  return (
+    <div className="flex gap-1 py-2">
+      {bands.map((band, index) => (
+        <div key={index} className="flex flex-col items-center gap-2 w-14">
+          <Slider
+            size="sm"
+            color="foreground"
+            orientation="vertical"
+            aria-label={`Band ${index + 1}`}
+            minValue={-12}
+            maxValue={12}
+            step={0.5}
+            classNames={{ base: 'h-40' }}
+            value={band.gain}
+            onChange={value => update(index, { gain: typeof value === 'number' ? value : value[0] }, false)}
+            onChangeEnd={value => update(index, { gain: typeof value === 'number' ? value : value[0] })}
+          />
+
+          <div className="text-tiny text-default-500">
+            {band.gain > 0 ? '+' : ''}
+            {band.gain}
+          </div>
+
+          <BandOptions band={band} onChange={value => update(index, value)} />
+        </div>
+      ))}
+    </div>
+  )
+}
+
+type BandOptionsProps = { band: Band; onChange: (band: Partial<Band>) => void }
+
+function BandOptions({ band, onChange }: BandOptionsProps) {
+  const [frequency, setFrequency] = useState(String(band.frequency))
+  const [q, setQ] = useState(String(band.q))
+
+  return (
+    <Popover placement="bottom" radius="sm">
+      <PopoverTrigger>
+        <Button size="sm" radius="sm" variant="light" className="min-w-0 px-1 text-tiny">
+          {formatFrequency(band.frequency)}
+        </Button>
+      </PopoverTrigger>
+
+      <PopoverContent>
+        <div className="w-48 flex flex-col gap-2 py-2">
+          <Select
+            size="sm"
+            label="Type"
+            radius="sm"
+            popoverProps={{ classNames: { content: 'rounded-small' } }}
+            classNames={{ listbox: 'px-0' }}
+            selectedKeys={[band.kind]}
+            onSelectionChange={value => {
+              const kind = value.currentKey as BandKind | undefined
+              if (kind) onChange({ kind })
+            }}>
+            {Object.entries(BAND_KINDS).map(([kind, label]) => (
+              <SelectItem key={kind}>{label}</SelectItem>
+            ))}
+          </Select>
+
+          <Input
+            size="sm"
+            radius="sm"
+            type="number"
+            label="Frequency (Hz)"
+            value={frequency}
+            onValueChange={setFrequency}
+            onBlur={() => {
+              const value = Number(frequency)
+              if (value >= 20 && value <= 20000) onChange({ frequency: value })
+              else setFrequency(String(band.frequency))
+            }}
+          />
+
+          <Input
+            size="sm"
+            radius="sm"
+            type="number"
+            label="Q"
+            value={q}
+            onValueChange={setQ}
+            onBlur={() => {
+              const value = Number(q)
+              if (value >= 0.1 && value <= 10) onChange({ q: value })
+              else setQ(String(band.q))
+            }}
+          />
+        </div>
+      </PopoverContent>
+    </Popover>
+  )
+}
+
+type PresetNameModalProps = {
+  isOpen: boolean
+  onOpenChange: (isOpen: boolean) => void
+  existing?: string
+  onAction: (name: string) => Promise<void>
+}
+
+function PresetNameModal({ isOpen, onOpenChange, existing, onAction }: PresetNameModalProps) {
+  const [name, setName] = useState(existing ?? '')
+  const title = existing ? 'Rename Preset' : 'New Preset'
+
+  return (
+    <Modal isOpen={isOpen} placement="bottom-center" backdrop="blur" radius="sm" onOpenChange={onOpenChange}>
+      <ModalContent>
+        <ModalHeader>{title}</ModalHeader>
+
+        <ModalBody>
+          <Input
+            autoFocus
+            radius="sm"
+            label="Name"
+            variant="flat"
+            value={name}
+            onValueChange={setName}
+            onClear={() => setName('')}
+            placeholder="Name of the preset"
+          />
+        </ModalBody>
+
+        <ModalFooter>
+          <Button
+            radius="sm"
+            variant="flat"
+            color="success"
+            isDisabled={!name.trim() || name === existing}
+            onPress={async () => {
+              try {
+                await onAction(name.trim())
+              } catch (err) {
+                addToast({ title, description: (err as Error).message, color: 'danger' })
+              }
+            }}>
+            <CheckIcon className="text-lg" /> {existing ? 'Save' : 'Create'}
+          </Button>
+        </ModalFooter>
+      </ModalContent>
+    </Modal>
+  )
+}
+
+type TrackEqualizerProps = { track: Track; className?: string }
+
+export function TrackEqualizer({ track, className }: TrackEqualizerProps) {
+  const queryPresets = useQuery({ queryKey: ['equalizer-presets'], queryFn: getPresets })
+
+  const query = useQuery({
+    queryKey: ['equalizer-assignment', track.hash],
+    queryFn: async () => await getAssignment(track),
+  })
+
+  const mutation = useMutation({
+    mutationFn: async ({ target, preset }: { target: 'track' | 'album'; preset: string | null }) => {
+      if (target === 'track') await setTrackPreset(track, preset)
+      else if (track.album) await setAlbumPreset(track.album, preset)
+    },
+    onError: err => addToast({ timeout: 5000, color: 'danger', title: err.message }),
+    onSuccess: () => {
+      query.refetch()
+      addToast({ color: 'success', title: 'Equalizer Saved' })
+    },
+  })
+
+  const items = [
+    <SelectItem key={NO_PRESET}>Not Set</SelectItem>,
+    ...(queryPresets.data ?? []).map(it => <SelectItem key={it.name}>{it.name}</SelectItem>),
+  ]
+
+  const onSelect = (target: 'track' | 'album') => (value: { currentKey?: string }) => {
+    const key = value.currentKey
+    if (!key) return
+
+    mutation.mutate({ target, preset: key === NO_PRESET ? null : key })
+  }
+
+  return (
+    <div className={className}>
+      <div className="text-small mb-4 text-default-500">
+        A preset set for this track is used over the one set for its album, which is used over the default one.
+      </div>
+
+      <Select
+        label="This Track"
+        radius="sm"
+        labelPlacement="outside"
+        popoverProps={{ classNames: { content: 'rounded-small' } }}
+        classNames={SELECT_CLASS_NAMES}
+        isDisabled={!query.isSuccess}
+        selectedKeys={[query.data?.track ?? NO_PRESET]}
+        onSelectionChange={onSelect('track')}>
+        {items}
+      </Select>
+
+      <Select
+        label="This Album"
+        radius="sm"
+        labelPlacement="outside"
+        popoverProps={{ classNames: { content: 'rounded-small' } }}
+        classNames={SELECT_CLASS_NAMES}
+        isDisabled={!query.isSuccess || !track.album}
+        selectedKeys={[query.data?.album ?? NO_PRESET]}
+        onSelectionChange={onSelect('album')}>
+        {items}
+      </Select>
+    </div>
+  )
+}
+
+function formatFrequency(value: number) {
+  return value >= 1000 ? `${Number((value / 1000).toFixed(1))}k` : String(value)
+}
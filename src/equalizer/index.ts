@@ -0,0 +1,72 @@
+import { invoke } from '@tauri-apps/api/core'
+import { normalizeError as normalizeCoreError } from '@/utils'
+import type { Track } from '@/tracks'
+
+export const BAND_KINDS = { peaking: 'Peak', lowShelf: 'Low Shelf', highShelf: 'High Shelf' }
+
+export type BandKind = keyof typeof BAND_KINDS
+// frequency in hz, gain in dB
+export type Band = { kind: BandKind; frequency: number; gain: number; q: number }
+export type EqualizerPreset = { name: string; bands: Band[] }
+// presets set for the track itself and for its album, the track's wins
+export type EqualizerAssignment = { track: string | null; album: string | null }
+
+// a flat 10 band graphic style layout to start new presets from
+export function createBands(): Band[] {
+  const frequencies = [31, 62, 125, 250, 500, 1000, 2000, 4000, 8000, 16000]
+
+  return frequencies.map((frequency, index) => ({
+    kind: index === 0 ? 'lowShelf' : index === frequencies.length - 1 ? 'highShelf' : 'peaking',
+    frequency,
+    gain: 0,
+    q: 1.41,
+  }))
+}
+
+export async function getPresets() {
+  return await invoke<EqualizerPreset[]>('db_get_equalizer_presets')
+}
+
+// adds the preset or replaces the bands of the one with the same name
+export async function setPreset(preset: EqualizerPreset) {
+  return await invoke('db_set_equalizer_preset', { preset })
+}
+
+export async function renamePreset(name: string, newName: string) {
+  try {
+    return await invoke('db_rename_equalizer_preset', { name, newName })
+  } catch (err) {
+    throw normalizeError(err)
+  }
+}
+
+export async function removePreset(name: string) {
+  return await invoke('db_remove_equalizer_preset', { name })
+}
+
+export async function getAssignment(track: Track) {
+  return await invoke<EqualizerAssignment>('db_get_equalizer_assignment', { hash: track.hash })
+}
+
+// `null` goes back to the album's preset, or the default one
+export async function setTrackPreset(track: Track, preset: string | null) {
+  return await invoke('db_set_track_equalizer', { hash: track.hash, preset })
+}
+
+export async function setAlbumPreset(album: string, preset: string | null) {
+  return await invoke('db_set_album_equalizer', { album, preset })
+}
+
+// used for tracks without a preset of their own or their album's, `null` plays them flat
+export async function setDefaultPreset(name: string | null) {
+  return await invoke('player_set_default_equalizer', { name })
+}
+
+function normalizeError(err: unknown) {
+  let error = normalizeCoreError(err)
+
+  if (error.message.includes('UNIQUE constraint failed: equalizer_presets.name'))
+    return new Error('A preset with the same name already exists')
+
+  return error
+}
@@ -15,6 +15,7 @@ import {
 } from '@heroui/react'
 import { CheckIcon } from 'lucide-react'
 import type { Disclosure } from '@/utils'
+import { getDuration } from '@/tracks'
 import type { Track } from '@/tracks'
 
 export type ExternalLyrics = {
@@ -33,7 +34,7 @@ export type Lyrics = { id?: string | number; plain: string; synced: string }
 
 export async function searchExternalLyrics(track: Track) {
   const res = await fetch(
-    `https://lrclib.net/api/search?artist_name=${track.artist}&track_name=${track.title}&album_name=${track.album}&duration=${track.duration}`,
+    `https://lrclib.net/api/search?artist_name=${track.artist}&track_name=${track.title}&album_name=${track.album}&duration=${getDuration(track)}`,
   )
   const data = (await res.json()) as ExternalLyrics[]
 
@@ -16,11 +16,12 @@ import ReactDOM from 'react-dom/client'
 import { QueryClient, QueryClientProvider } from '@tanstack/react-query'
 import { createBrowserRouter, RouterProvider } from 'react-router'
 import { getCurrentWindow } from '@tauri-apps/api/window'
+import { listen } from '@tauri-apps/api/event'
 import {
   register as registerGlobalShortcut,
   isRegistered as isGlobalShortcutRegistered,
 } from '@tauri-apps/plugin-global-shortcut'
-import { ToastProvider } from '@heroui/react'
+import { ToastProvider, addToast } from '@heroui/react'
 import { init as initPlayer, onGlobalShortcut as onPlayerGlobalShortcut } from '@/player'
 import { Window } from '@/components/window'
 import { HomeScreen } from '@/components'
@@ -31,7 +32,7 @@ import { EmotionsScreen, EmotionScreen } from '@/emotions/components'
 import { QueueScreen } from '@/queue'
 import { AlbumsScreen } from '@/albums'
 import { ArtistsScreen } from '@/artists'
-import { SettingsScreen, init as initSettings } from '@/settings'
+import { SettingsScreen, init as initSettings, type WatchError } from '@/settings'
 
 const currentWindow = getCurrentWindow()
 
@@ -64,6 +65,22 @@ const queryClient = new QueryClient({
 await initSettings()
 await initPlayer()
 
+// the backend watches the library folders and reports whenever tracks were added, changed or removed
+await listen('library-changed', () => queryClient.invalidateQueries())
+
+// a folder that can't be watched or changes that couldn't be saved, changes to it are missed until the next scan
+await listen<WatchError[]>('library-watch-error', ({ payload }) => {
+  queryClient.invalidateQueries({ queryKey: ['watch-errors'] })
+
+  addToast({
+    timeout: 5000,
+    color: 'danger',
+    title: 'Library changes may be missed',
+    description: payload.map(err => (err.path ? `${err.path}: ${err.message}` : err.message)).join('
+'),
+  })
+})
+
 ReactDOM.createRoot(document.getElementById('root')!).render(
   <React.StrictMode>
     <QueryClientProvider client={queryClient}>
@@ -1,10 +1,11 @@
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 import { Link } from 'react-router'
 import { useQuery } from '@tanstack/react-query'
-import { Button, Image, cn } from '@heroui/react'
+import { Button, Image, Popover, PopoverContent, PopoverTrigger, Slider, cn } from '@heroui/react'
 import { useStore } from 'zustand'
 import {
   AudioLinesIcon,
+  GaugeIcon,
   LetterTextIcon,
   PictureInPicture2Icon,
   Repeat1Icon,
@@ -20,7 +21,7 @@ import { store, setMiniPlayerVisibility, setPlayerMaximized } from '@/settings'
 import { PlayButton, SeekBar } from '@/players'
 import { usePlayer } from '@/player'
 import { getLyrics, PlainLyricsView, SyncedLyricsView } from '@/lyrics'
-import { normalizeMeta } from '@/tracks'
+import { getDuration, normalizeMeta } from '@/tracks'
 import { AlbumLink, ArtistLink, Cover } from '@/tracks/components/details'
 
 type PlayerProps = { mini?: boolean }
@@ -52,7 +53,7 @@ export function Player({ mini }: PlayerProps) {
 
       {mini ? (
         <div className="flex w-full px-8 gap-3">
-          <Cover url={player.current?.cover} className="size-40 shrink-0" />
+          <Cover url={player.current?.cover} size={160} className="size-40 shrink-0" />
 
           <div className="flex flex-col gap-2">
             {meta.title && <div className="text-large">{meta.title}</div>}
@@ -71,7 +72,7 @@ export function Player({ mini }: PlayerProps) {
             <SyncedLyricsView
               data={queryLyrics.data.synced}
               elapsed={player.elapsed}
-              duration={player.current?.duration}
+              duration={getDuration(player.current)}
               onSeek={player.seek}
               className="h-80"
             />
@@ -92,7 +93,7 @@ export function Player({ mini }: PlayerProps) {
         <SeekBar
           onSeek={player.seek}
           elapsed={player.elapsed}
-          duration={player.current?.duration}
+          duration={getDuration(player.current)}
           isDisabled={!player.current || !!player.error}
         />
 
@@ -158,6 +159,8 @@ export function Player({ mini }: PlayerProps) {
           <ShuffleIcon className="text-lg" />
         </Button>
 
+        <SpeedControls />
+
         {!mini && (
           <Button
             as={Link}
@@ -179,6 +182,75 @@ export function Player({ mini }: PlayerProps) {
   )
 }
 
+function SpeedControls() {
+  const player = usePlayer()
+  const [rate, setRate] = useState(player.rate * 100)
+  const [pitch, setPitch] = useState(player.pitch)
+
+  // case: changed from elsewhere, e.g. by a rule or a reset
+  useEffect(() => setRate(player.rate * 100), [player.rate])
+  useEffect(() => setPitch(player.pitch), [player.pitch])
+
+  const isChanged = player.rate !== 1 || player.pitch !== 0
+
+  return (
+    <Popover placement="top" containerPadding={8} radius="sm">
+      <PopoverTrigger>
+        <Button
+          isIconOnly
+          radius="full"
+          isDisabled={!player.current}
+          variant={isChanged ? 'flat' : 'light'}
+          color={isChanged ? 'warning' : 'default'}>
+          <GaugeIcon className="text-lg" />
+        </Button>
+      </PopoverTrigger>
+
+      <PopoverContent>
+        <div className="w-72 flex flex-col gap-3 py-2">
+          <Slider
+            size="sm"
+            label="Speed"
+            color="foreground"
+            minValue={25}
+            maxValue={200}
+            step={5}
+            getValue={value => `${value}%`}
+            value={rate}
+            onChangeEnd={() => player.setRate(rate / 100)}
+            onChange={value => setRate(typeof value === 'number' ? value : value[0])}
+          />
+
+          <Slider
+            size="sm"
+            label="Pitch"
+            color="foreground"
+            minValue={-12}
+            maxValue={12}
+            step={1}
+            getValue={value => `${Number(value) > 0 ? '+' : ''}${value} st`}
+            value={pitch}
+            onChangeEnd={() => player.setPitch(pitch)}
+            onChange={value => setPitch(typeof value === 'number' ? value : value[0])}
+          />
+
+          <Button
+            size="sm"
+            radius="sm"
+            variant="flat"
+            isDisabled={!isChanged}
+            onPress={async () => {
+              await player.setRate(1)
+              await player.setPitch(0)
+            }}>
+            Reset
+          </Button>
+        </div>
+      </PopoverContent>
+    </Popover>
+  )
+}
+
 export function MiniPlayer() {
   return (
     <div
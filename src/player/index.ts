@@ -4,9 +4,10 @@ import { invoke } from '@tauri-apps/api/core'
 import { addToast } from '@heroui/react'
 import { Interval } from '@/utils'
 import { rankUp } from '@/emotions'
-import { setMiniPlayerVisibility, setPlayerMaximized } from '@/settings'
+import { setGlobalVolume, setMiniPlayerVisibility, setPlayerMaximized } from '@/settings'
 import { BackendPlayer, WebPlayer } from '@/player/types'
 import type { ShortcutHandler } from '@tauri-apps/plugin-global-shortcut'
+import { getDuration } from '@/tracks'
 import type { Track } from '@/tracks'
 import type { Player } from '@/player/types'
 
@@ -23,7 +24,8 @@ function initialState(): Store {
     repeat: null,
     template: null,
     isShuffled: false,
-    backupQueue: [],
+    rate: 1,
+    pitch: 0,
     player: backendPlayer,
   }
 }
@@ -53,7 +55,7 @@ export async function goto(index: number, using?: Player) {
         const newQueue = Array.from(queue)
         const duration = await player.getDuration()
 
-        newQueue[index].duration = duration
+        newQueue[index].duration = duration * 1000
         queue = newQueue
       }
 
@@ -83,10 +85,11 @@ export async function seek(elapsed: number) {
   if (state.elapsed === elapsed) return
 
   if (!state.isPaused) interval.stop()
-  await state.player.seek(elapsed)
+  const reached = await state.player.seek(elapsed)
   if (!state.isPaused) interval.start()
 
-  store.setState({ elapsed })
+  // where the backend actually landed, in fractional seconds (it seeks to the millisecond)
+  store.setState({ elapsed: reached })
 }
 
 export async function pause() {
@@ -115,44 +118,29 @@ export async function playTracks(data: Track | Track[], from = 0) {
   // if (!queue.length) throw new Error('No tracks to play')
 
   await setQueue(queue)
-  store.setState({ queue, isShuffled: false, backupQueue: [] })
+  store.setState({ queue, isShuffled: false })
 
   await goto(from)
   await play()
 }
 
-export async function shuffle() {
-  const state = store.getState()
-  const current = state.queue.at(state.current)
-  if (state.isShuffled) return
-
-  const shuffled = state.queue.toSorted(() => Math.random() - 0.5)
-  const index = shuffled.findIndex(it => it === current)
-  const backupQueue = state.queue
-
-  await setCurrent(index)
-  await setQueue(shuffled)
-
-  store.setState({ isShuffled: true, backupQueue, current: index, queue: shuffled })
+// the current track moves to the front, a seed gives the same order every time
+export async function shuffle(seed?: number) {
+  if (store.getState().isShuffled) return
+  await applyQueueState(await invoke<QueueState>('player_shuffle', { seed: seed ?? null }))
 }
 
 export async function unshuffle() {
-  const state = store.getState()
-  const current = state.queue.at(state.current)
-  if (!state.isShuffled) return
-
-  const original = state.backupQueue
-  const index = original.findIndex(it => it === current)
-
-  await setCurrent(index)
-  await setQueue(original)
-
-  store.setState({ isShuffled: false, backupQueue: [], current: index, queue: original })
+  if (!store.getState().isShuffled) return
+  await applyQueueState(await invoke<QueueState>('player_unshuffle'))
 }
 
 export async function reset() {
   await setQueue([])
   await setCurrent(0)
+  await invoke('player_set_repeat', { repeat: null })
+  await setRate(1)
+  await setPitch(0)
   await stop()
 
   interval.stop()
@@ -163,13 +151,27 @@ function onElapsed() {
   const state = store.getState()
   const current = state.queue.at(state.current)
 
+  // case: the backend player keeps the clock and moves on by itself, both reported through events
+  if (state.player === backendPlayer) return
+
   if (!current) return reset()
-  if (state.elapsed >= current.duration) return next()
+  if (state.elapsed >= getDuration(current)) return next()
+
+  setElapsed(state.elapsed + 1)
+}
 
-  store.setState(state => ({ elapsed: state.elapsed + 1 }))
+function setElapsed(elapsed: number) {
+  const state = store.getState()
+  const current = state.queue.at(state.current)
+  if (!current || state.elapsed === elapsed) return
+
+  store.setState({ elapsed })
 
-  // n seconds before the end, not awaiting the promise
-  if (isEmotionRankingAllowed(state.template) && current.duration - state.elapsed === 10) {
+  // n seconds before the end, once when that second is reached, not awaiting the promise
+  const second = Math.floor(elapsed)
+  const isNewSecond = second !== Math.floor(state.elapsed)
+
+  if (isEmotionRankingAllowed(state.template) && isNewSecond && getDuration(current) - second === 10) {
     rankUp(current)
   }
 }
@@ -182,19 +184,18 @@ async function playArbitraryTracks(data: Track | Track[]) {
   setMiniPlayerVisibility(true)
 }
 
+// the backend owns the queue, so it decides where next and previous lead
 export async function next() {
-  const state = store.getState()
-  const index = getNextIndex(state)
+  const index = await invoke<number | null>('player_get_next_index')
 
-  if (index === -1) return await reset()
+  if (index === null) return await reset()
   await goto(index)
 }
 
 export async function prev() {
-  const state = store.getState()
-  const index = getPrevIndex(state)
+  const index = await invoke<number | null>('player_get_prev_index')
 
-  if (index === -1) return await reset()
+  if (index === null) return await reset()
   await goto(index)
 }
 
@@ -202,19 +203,48 @@ export function setTemplate(template: Template | null) {
   store.setState({ template })
 }
 
-export function setRepeat(repeat: Repeat | null) {
+export async function setRepeat(repeat: Repeat | null) {
+  await invoke('player_set_repeat', { repeat })
   store.setState({ repeat })
 }
 
+// NOTE: tracks already in the queue are skipped by hash, as they can be from different sources
 export async function extendQueue(tracks: Track[]) {
+  const entries = tracks.map(t => ({ hash: t.hash, path: t.path }))
+  await applyQueueState(await invoke<QueueState>('player_append', { entries }), tracks)
+}
+
+// NOTE: plays the tracks right after the current one, moving those already in the queue
+export async function insertNext(tracks: Track[]) {
+  const entries = tracks.map(t => ({ hash: t.hash, path: t.path }))
+  await applyQueueState(await invoke<QueueState>('player_insert_next', { entries }), tracks)
+}
+
+export async function removeFromQueue(indexes: number[]) {
+  const playing = store.getState().queue.at(store.getState().current)
+  await applyQueueState(await invoke<QueueState>('player_remove', { indexes }))
+
+  // case: the playing track was removed, the backend moved on to the one that took its place
   const state = store.getState()
+  if (state.queue.at(state.current) === playing) return
 
-  // NOTE: not comparing by reference because the tracks can be from different sources
-  const filtered = tracks.filter(track => !state.queue.some(it => it.hash === track.hash))
-  const queue = state.queue.concat(filtered)
+  if (state.player === webPlayer) await goto(state.current, webPlayer)
+  else store.setState({ elapsed: 0 })
+}
 
-  await setQueue(queue)
-  store.setState({ queue })
+export async function moveInQueue(src: number, dst: number) {
+  await applyQueueState(await invoke<QueueState>('player_move', { from: src, to: dst }))
+}
+
+// lines the tracks up with the order the backend reports, `added` being any not in the queue yet
+async function applyQueueState({ hashes, current, repeat, shuffleSeed }: QueueState, added: Track[] = []) {
+  const known = new Map(store.getState().queue.concat(added).map(t => [t.hash, t]))
+  const queue = hashes.map(hash => known.get(hash)).filter(t => t !== undefined)
+
+  await webPlayer.setQueue(queue)
+  await webPlayer.setCurrent(current)
+
+  store.setState({ queue, current, repeat, isShuffled: shuffleSeed !== null })
 }
 
 async function stop() {
@@ -237,13 +267,29 @@ export async function setVolume(volume: number) {
   await webPlayer.setVolume(volume)
 }
 
+// 1 is normal speed, the pitch stays the same
+export async function setRate(rate: number) {
+  await backendPlayer.setRate(rate)
+  await webPlayer.setRate(rate)
+
+  store.setState({ rate })
+}
+
+// in semitones, the speed stays the same
+export async function setPitch(pitch: number) {
+  await backendPlayer.setPitch(pitch)
+  await webPlayer.setPitch(pitch)
+
+  store.setState({ pitch })
+}
+
 export function usePlayer() {
   const state = useStore(store)
 
   // TODO: remove standalone functions
   return {
     setState: store.setState,
-    toggleShuffle: state.isShuffled ? unshuffle : shuffle,
+    toggleShuffle: state.isShuffled ? unshuffle : () => shuffle(),
     togglePlay: state.isPaused ? play : pause,
     current: state.queue.at(state.current),
     isRepeatCurrent: isRepeatCurrent(state.repeat),
@@ -254,11 +300,17 @@ export function usePlayer() {
     elapsed: state.elapsed,
     repeat: state.repeat,
     queue: state.queue,
+    rate: state.rate,
+    pitch: state.pitch,
     error: state.error,
+    removeFromQueue,
+    moveInQueue,
     setTemplate,
     setCurrent,
     playTracks,
     setVolume,
+    setRate,
+    setPitch,
     setRepeat,
     setQueue,
     reset,
@@ -281,14 +333,48 @@ export async function init() {
     await playArbitraryTracks(evt.payload)
   })
 
+  listen<number>('player-track-changed', async evt => {
+    const state = store.getState()
+    if (state.player !== backendPlayer) return
+
+    store.setState({ current: evt.payload, elapsed: 0 })
+  })
+
+  listen('player-ended', async () => {
+    if (store.getState().player !== backendPlayer) return
+    await next()
+  })
+
+  listen<PlayerStatus>('player-status', evt => {
+    const state = store.getState()
+    const { index, position } = evt.payload
+    if (state.player !== backendPlayer || index !== state.current) return
+
+    setElapsed(position)
+  })
+
   const tracks = await getArbitraryTracks()
-  if (!tracks.length) return
+  if (!tracks.length) return await restoreSession()
 
   await playArbitraryTracks(tracks)
 }
 
+// picks up where the last run stopped, paused
+async function restoreSession() {
+  const session = await invoke<Session | null>('player_restore_session')
+  if (!session?.tracks.length) return
+
+  setGlobalVolume(session.volume)
+  await applyQueueState(session.state, session.tracks)
+
+  await goto(session.state.current)
+  await seek(session.position / 1000)
+}
+
 type GetIndexParams = Pick<Store, 'queue' | 'current' | 'repeat'>
 
+// mirror `Queue::next_index` and `Queue::prev_index` in the backend, only used to enable the controls
+
 export function getNextIndex({ queue, current, repeat }: GetIndexParams) {
   if (isRepeatCurrent(repeat)) return current
   if (queue.length > current + 1) return current + 1
@@ -330,6 +416,12 @@ export const onGlobalShortcut: ShortcutHandler = evt => {
 
 export type Template = 'emotions' | 'arbitrary'
 
+type PlayerStatus = { index: number | null; position: number; duration: number | null; paused: boolean }
+
+type Session = { tracks: Track[]; state: QueueState; position: number; volume: number }
+
+type QueueState = { hashes: string[]; current: number; repeat: Repeat | null; shuffleSeed: number | null }
+
 export type Repeat = 'current' | 'all'
 
 export type Store = {
@@ -340,8 +432,8 @@ export type Store = {
   repeat: Repeat | null
   template: Template | null
   isShuffled: boolean
-  // backup queue for reverting shuffle
-  backupQueue: Track[]
+  rate: number
+  pitch: number
   error?: Error | null
   player: Player
 }
@@ -12,8 +12,10 @@ export class BackendPlayer implements Player {
     await this.pause()
   }
 
+  // returns the position actually reached, the backend works in milliseconds
   async seek(elapsed: number) {
-    await invoke('player_seek', { elapsed })
+    const position = await invoke<number>('player_seek', { position: Math.round(elapsed * 1000) })
+    return position / 1000
   }
 
   async pause() {
@@ -25,7 +27,7 @@ export class BackendPlayer implements Player {
   }
 
   async setQueue(queue: Track[]) {
-    await invoke('player_set_queue', { queue: queue.map(t => t.path) })
+    await invoke('player_set_queue', { queue: queue.map(t => ({ hash: t.hash, path: t.path })) })
   }
 
   async setCurrent(current: number) {
@@ -35,6 +37,14 @@ export class BackendPlayer implements Player {
   async setVolume(volume: number) {
     await invoke('player_set_volume', { volume })
   }
+
+  async setRate(rate: number) {
+    await invoke('player_set_rate', { rate })
+  }
+
+  async setPitch(pitch: number) {
+    await invoke('player_set_pitch', { pitch })
+  }
 }
 
 export class WebPlayer implements Player {
@@ -61,7 +71,7 @@ export class WebPlayer implements Player {
   }
 
   async seek(elapsed: number) {
-    this.player.seek(elapsed)
+    return this.player.seek(elapsed)
   }
 
   async pause() {
@@ -84,6 +94,13 @@ export class WebPlayer implements Player {
     this.player.setVolume(volume)
   }
 
+  async setRate(rate: number) {
+    this.player.setRate(rate)
+  }
+
+  // the browser can only keep the pitch while changing speed, not shift it
+  async setPitch(_: number) {}
+
   async getDuration() {
     return await this.player.getDuration()
   }
@@ -91,11 +108,13 @@ export class WebPlayer implements Player {
 
 export type Player = {
   goto(index: number): Promise<void>
-  seek(elapsed: number): Promise<void>
+  seek(elapsed: number): Promise<number>
   pause(): Promise<void>
   play(): Promise<void>
   stop(): Promise<void>
   setVolume(volume: number): Promise<void>
+  setRate(rate: number): Promise<void>
+  setPitch(pitch: number): Promise<void>
   setQueue(queue: Track[]): Promise<void>
   setCurrent(current: number): Promise<void>
 }
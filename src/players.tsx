@@ -23,6 +23,7 @@ export class MediaPlayer {
 
   seek(elapsed: number) {
     this.core.currentTime = elapsed
+    return this.core.currentTime
   }
 
   async getDuration() {
@@ -46,6 +47,13 @@ export class MediaPlayer {
   setVolume(volume: number) {
     this.core.volume = volume
   }
+
+  // loading a new source goes back to the default rate, so that one is set too
+  setRate(rate: number) {
+    this.core.preservesPitch = true
+    this.core.defaultPlaybackRate = rate
+    this.core.playbackRate = rate
+  }
 }
 
 type PlayButtonProps = { isPaused: boolean; error?: Error | null; current?: Track | null; toggle: () => void }
@@ -17,6 +17,7 @@ import {
 import {
   CheckIcon,
   CopyIcon,
+  ListStartIcon,
   ListVideoIcon,
   MoveLeftIcon,
   PlayIcon,
@@ -27,7 +28,7 @@ import {
 } from 'lucide-react'
 import { reorder, isEditorOfType } from '@/utils'
 import { setMiniPlayerVisibility, setPlayerMaximized } from '@/settings'
-import { extendQueue, usePlayer } from '@/player'
+import { extendQueue, insertNext, usePlayer } from '@/player'
 import {
   addPlaylist,
   addPlaylistTracks,
@@ -142,6 +143,16 @@ export function PlaylistScreen() {
               }}>
               <ListVideoIcon className="text-lg" /> Add to Queue
             </Button>
+
+            <Button
+              radius="sm"
+              variant="flat"
+              onPress={async () => {
+                await insertNext(selection.values)
+                selection.clear()
+              }}>
+              <ListStartIcon className="text-lg" /> Play Next
+            </Button>
           </>
         ) : (
           <>
@@ -18,25 +18,11 @@ export function QueueScreen() {
     // case: remove all tracks when selection is empty, reset player state
     if (!selection.values.length) return await player.reset()
 
-    const filtered = player.queue.filter(track => !selection.isSelected(track))
+    const indexes = player.queue.flatMap((track, index) => (selection.isSelected(track) ? [index] : []))
 
     // case: if remaining queue is empty, reset player state
-    if (!filtered.length) await player.reset()
-    else {
-      await player.setQueue(filtered)
-      const index = filtered.findIndex(track => track === player.current)
-
-      // case: current track was not removed
-      if (index !== -1) {
-        await player.setCurrent(index)
-        player.setState({ queue: filtered, current: index })
-      } else {
-        // case: current track was removed
-        player.setState({ queue: filtered })
-        await player.goto(0)
-        await player.pause()
-      }
-    }
+    if (indexes.length === player.queue.length) await player.reset()
+    else await player.removeFromQueue(indexes)
 
     selection.clear()
   }
@@ -54,8 +40,7 @@ export function QueueScreen() {
     // optimistic update for smooth user experience
     player.setState({ queue: reordered, current: index })
 
-    await player.setQueue(reordered)
-    await player.setCurrent(index)
+    await player.moveInQueue(src, dst)
   }
 
   return (
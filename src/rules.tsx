@@ -2,6 +2,7 @@ import { useEffect, useRef } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import { Input, Button, Accordion, AccordionItem } from '@heroui/react'
 import { TrashIcon, PlusIcon, XIcon, CheckIcon } from 'lucide-react'
+import { getDuration } from '@/tracks'
 import type { Track } from '@/tracks'
 
 type RulesEditorProps = { track: Track; values: string[]; setValues: (values: string[]) => void }
@@ -108,7 +109,7 @@ function parseRule(rule: string, track: Track): Rule | null {
 
   switch (parts[2]) {
     case 'goto': {
-      if (parts[3] === 'next') return { trigger, action: 'seek', param: track.duration }
+      if (parts[3] === 'next') return { trigger, action: 'seek', param: getDuration(track) }
 
       const param = parseTime(parts[3])
       if (param === null) return null
@@ -130,6 +131,13 @@ function parseRule(rule: string, track: Track): Rule | null {
       return { trigger, action: 'volume', param }
     }
 
+    case 'speed': {
+      const param = parseInt(parts[3])
+      if (isNaN(param)) return null
+
+      return { trigger, action: 'speed', param }
+    }
+
     default:
       return null
   }
@@ -144,7 +152,7 @@ export function parseRules(track: Track | null) {
     .filter(Boolean) as Rule[]
 }
 
-export type Rule = { trigger: number; action: 'seek' | 'volume'; param: number }
+export type Rule = { trigger: number; action: 'seek' | 'volume' | 'speed'; param: number }
 
 const TIME_REGEX = /^(?:(\d{1,2}):)?([0-5]?\d):([0-5]?\d)$|^([0-5]?\d)$/
 
@@ -175,16 +183,20 @@ type UseExecuteRulesOptions = {
   track: Track | null
   seek: (elapsed: number) => void
   setVolume: (volume: number) => void
+  setRate?: (rate: number) => void
   enabled?: boolean
 }
 
-export function useExecuteRules({ track, elapsed, seek, setVolume, enabled = true }: UseExecuteRulesOptions) {
+export function useExecuteRules({ track, elapsed, seek, setVolume, setRate, enabled = true }: UseExecuteRulesOptions) {
   // NOTE: each rule is executed once per track
   // if you play the same track from the same source, the rules will be skipped
   // because the track reference is the same
 
+  // rules trigger on whole seconds, the position itself is fractional
+  const second = Math.floor(elapsed)
+
   const rules = useRef<Map<string, Rule>>(new Map())
-  const prevElapsed = useRef(elapsed)
+  const prevElapsed = useRef(second)
 
   const parse = () =>
     !track
@@ -203,23 +215,23 @@ export function useExecuteRules({ track, elapsed, seek, setVolume, enabled = tru
 
   useEffect(() => {
     if (!enabled || !rules.current.size || !track) {
-      prevElapsed.current = elapsed
+      prevElapsed.current = second
       return
     }
 
-    const key = `${track.hash}-${elapsed}`
+    const key = `${track.hash}-${second}`
     const rule = rules.current.get(key)
 
     switch (rule?.action) {
       case 'seek': {
-        const value = Math.min(Math.max(rule.param, 0), track.duration)
+        const value = Math.min(Math.max(rule.param, 0), getDuration(track))
 
-        if (value === elapsed) break
+        if (value === second) break
         seek(value)
 
         // NOTE: workaround for seek sync issue, this will case a seekbar jump
         // also, this won't cause the rule to be deleted when it jumps back
-        if (prevElapsed.current !== elapsed) rules.current.delete(key)
+        if (prevElapsed.current !== second) rules.current.delete(key)
         break
       }
 
@@ -227,18 +239,23 @@ export function useExecuteRules({ track, elapsed, seek, setVolume, enabled = tru
         const value = Math.min(Math.max(rule.param, 0), 100)
         setVolume(value)
 
+        rules.current.delete(key)
+        break
+
+      case 'speed':
+        // as a percentage, same as the player allows
+        setRate?.(Math.min(Math.max(rule.param, 25), 400) / 100)
+
         rules.current.delete(key)
         break
     }
 
-    prevElapsed.current = elapsed
-  }, [enabled, elapsed, track])
+    prevElapsed.current = second
+  }, [enabled, second, track])
 
   return { reset }
 }
 
-// TODO: speed rules
-
 const DESCRIPTION = ` 
 Syntax
 
@@ -246,10 +263,12 @@ at hh:mm:ss goto    hh:mm:ss
 at hh:mm:ss goto    next
 at hh:mm:ss seek    +/-ss
 at hh:mm:ss volume  n
+at hh:mm:ss speed   n
 
 - hh and mm are optional, ss is required
 - mm and ss cannot be greater than 59
 - no duplicate trigger times or rules
+- speed is a percentage from 25 to 400, the pitch stays the same
 
 Examples
 
@@ -258,4 +277,5 @@ at 3:10  goto    next
 at 8     seek   +10
 at 2:08  seek   -28
 at 0:40  volume  60
+at 0     speed   75
 `
@@ -4,7 +4,7 @@ import { ChevronsLeftIcon, ChevronsRightIcon, PauseIcon, PlayIcon, RotateCcwIcon
 import { formatTime } from '@/utils'
 import { SeekBar } from '@/players'
 import { useScrubPlayer } from '@/scrub-player'
-import { normalizeMeta } from '@/tracks'
+import { getDuration, normalizeMeta } from '@/tracks'
 import { AlbumLink, ArtistLink, Cover } from '@/tracks/components/details'
 import type { Track } from '@/tracks'
 
@@ -23,7 +23,7 @@ export function ScrubPlayer({ onReplay, isRulesEnabled, onToggleRules }: ScrubPl
   return (
     <div className="flex flex-col items-center justify-center h-full isolate pb-6 pt-3">
       <div className="flex w-full px-8 gap-3">
-        <Cover url={player.current?.cover} className="size-40 shrink-0" />
+        <Cover url={player.current?.cover} size={160} className="size-40 shrink-0" />
 
         <div className="flex flex-col gap-2">
           {meta.title && <div className="text-large">{meta.title}</div>}
@@ -37,7 +37,7 @@ export function ScrubPlayer({ onReplay, isRulesEnabled, onToggleRules }: ScrubPl
         <SeekBar
           onSeek={player.seek}
           elapsed={player.elapsed}
-          duration={player.current?.duration}
+          duration={getDuration(player.current)}
           isDisabled={!player.current || !!player.error}
         />
 
@@ -94,7 +94,7 @@ export function ScrubPlayer({ onReplay, isRulesEnabled, onToggleRules }: ScrubPl
             variant="flat"
             className="rounded-r-small"
             isDisabled={!seekBy}
-            onPress={() => player.seek(Math.min(player.current?.duration ?? 0, player.elapsed + seekBy))}>
+            onPress={() => player.seek(Math.min(getDuration(player.current), player.elapsed + seekBy))}>
             <ChevronsRightIcon className="text-lg" />
           </Button>
         </div>
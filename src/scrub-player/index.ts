@@ -2,6 +2,7 @@ import { createStore, useStore } from 'zustand'
 import { addToast } from '@heroui/react'
 import { Interval } from '@/utils'
 import { BackendPlayer, WebPlayer } from '@/scrub-player/types'
+import { getDuration } from '@/tracks'
 import type { Track } from '@/tracks'
 import type { Player } from '@/scrub-player/types'
 
@@ -36,7 +37,7 @@ export async function start({ ...track }: Track, using?: Player) {
       // case: replace with metadata from the web player
       if (player instanceof WebPlayer) {
         const duration = await player.getDuration()
-        track.duration = duration
+        track.duration = duration * 1000
       }
 
       // case: continue the playback if the player is not paused while navigating
@@ -64,10 +65,10 @@ export async function seek(elapsed: number) {
   const state = store.getState()
 
   if (!state.isPaused) interval.stop()
-  await state.player.seek(elapsed)
+  const reached = await state.player.seek(elapsed)
   if (!state.isPaused) interval.start()
 
-  store.setState({ elapsed })
+  store.setState({ elapsed: reached })
 }
 
 export async function pause() {
@@ -104,7 +105,7 @@ function onElapsed() {
 
   if (!current) return reset()
 
-  if (state.elapsed >= current.duration) {
+  if (state.elapsed >= getDuration(current)) {
     // we can pause and start instead of stop and start ?
     // since the track is already loaded ?
     return pause().then(() => start(current))
@@ -10,8 +10,10 @@ export class BackendPlayer implements Player {
     await invoke('scrub_player_set_current', { path })
   }
 
+  // returns the position actually reached, the backend works in milliseconds
   async seek(elapsed: number) {
-    await invoke('scrub_player_seek', { elapsed })
+    const position = await invoke<number>('scrub_player_seek', { position: Math.round(elapsed * 1000) })
+    return position / 1000
   }
 
   async pause() {
@@ -49,7 +51,7 @@ export class WebPlayer implements Player {
   }
 
   async seek(elapsed: number) {
-    this.player.seek(elapsed)
+    return this.player.seek(elapsed)
   }
 
   async pause() {
@@ -73,7 +75,7 @@ export class WebPlayer implements Player {
 export type Player = {
   start(): Promise<void>
   setCurrent(path: string | null): Promise<void>
-  seek(elapsed: number): Promise<void>
+  seek(elapsed: number): Promise<number>
   pause(): Promise<void>
   play(): Promise<void>
   stop(): Promise<void>
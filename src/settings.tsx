@@ -1,4 +1,4 @@
-import { useState } from 'react'
+import { useEffect, useState } from 'react'
 import {
   Accordion,
   AccordionItem,
@@ -18,6 +18,7 @@ import {
 } from '@heroui/react'
 import { useMutation, useQuery } from '@tanstack/react-query'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { getName, getVersion } from '@tauri-apps/api/app'
 import { revealItemInDir, openUrl } from '@tauri-apps/plugin-opener'
 import { open } from '@tauri-apps/plugin-dialog'
@@ -34,34 +35,67 @@ import {
   XIcon,
 } from 'lucide-react'
 import { setVolume as setPlayerVolume } from '@/player'
+import { setDefaultPreset as setDefaultEqualizer } from '@/equalizer'
+import { EqualizerSettings } from '@/equalizer/components'
 
 const FONTS = ['Inter', 'Poppins', 'Merriweather', 'Dancing Script']
+const NORMALIZATION_MODES = { off: 'Off', track: 'Track Gain', album: 'Album Gain' }
+const FADE_CURVES = { equalPower: 'Equal Power', linear: 'Linear', sCurve: 'S-Curve' }
+
+// device names are free-form, so this can't clash with one
+const SYSTEM_OUTPUT = '\0system'
 
 export const DEFAULT_EMOTION = 'Neutral'
 
 export function SettingsScreen() {
   const state = useStore(store)
   const [fontSize, setFontSize] = useState(state.fontSize)
+  const [preamp, setPreamp] = useState(state.normalization.preamp)
+  const [crossfadeLength, setCrossfadeLength] = useState(state.crossfade.length)
   const resetModal = useDisclosure()
 
   const queryDirs = useQuery({ queryKey: ['dirs'], queryFn: getDirs })
+  const queryScanErrors = useQuery({ queryKey: ['scan-errors'], queryFn: getScanErrors })
+  const queryWatchErrors = useQuery({ queryKey: ['watch-errors'], queryFn: getWatchErrors })
+  const queryOutputDevices = useQuery({ queryKey: ['output-devices'], queryFn: getOutputDevices })
 
   const queryApp = useQuery({
     queryKey: ['app'],
     queryFn: async () => ({ name: await getName(), version: await getVersion() }),
   })
 
+  const [scanProgress, setScanProgress] = useState<ScanProgress | null>(null)
+
+  useEffect(() => {
+    const unlisten = listen<ScanProgress>('scan-progress', evt => setScanProgress(evt.payload))
+    return () => void unlisten.then(fn => fn())
+  }, [])
+
+  const onScanned = (result: ScanSummary) => {
+    const errors = result.errors.map(e => `[${e.kind}] ${e.path} : ${e.message}`)
+    const summary = `Added ${result.added}, updated ${result.updated} and removed ${result.removed} tracks with ${result.errors.length} errors.`
+
+    addToast({
+      timeout: 5000,
+      title: 'Folders Scanned',
+      description: [...errors, summary].join('\n\n'),
+      color: result.errors.length > 0 ? 'danger' : 'success',
+    })
+
+    queryScanErrors.refetch()
+  }
+
   const mutationScan = useMutation({
     mutationFn: scanDirs,
+    onMutate: () => setScanProgress(null),
     onError: err => addToast({ timeout: 5000, color: 'danger', title: err.message }),
-    onSuccess: result => {
-      addToast({
-        timeout: 5000,
-        title: 'Folders Scanned',
-        description: result.trim(),
-        color: result.startsWith('[ERR]') ? 'danger' : 'success',
-      })
-    },
+    onSuccess: onScanned,
+  })
+
+  const mutationRetryScan = useMutation({
+    mutationFn: retryScanErrors,
+    onError: err => addToast({ timeout: 5000, color: 'danger', title: err.message }),
+    onSuccess: onScanned,
   })
 
   const mutationBackup = useMutation({
@@ -96,6 +130,7 @@ export function SettingsScreen() {
     onSuccess: () => {
       addToast({ timeout: 5000, color: 'success', title: 'Database Reset' })
       queryDirs.refetch()
+      queryWatchErrors.refetch()
     },
   })
 
@@ -122,6 +157,7 @@ export function SettingsScreen() {
                   onPress={async () => {
                     await setDirs(queryDirs.data.filter(d => d !== dir))
                     await queryDirs.refetch()
+                    await queryWatchErrors.refetch()
                   }}>
                   <XIcon className="text-medium !text-foreground" />
                 </Button>
@@ -130,6 +166,16 @@ export function SettingsScreen() {
           </div>
         )}
 
+        {!!queryWatchErrors.data?.length && (
+          <div className="flex flex-col gap-1">
+            {queryWatchErrors.data.map(err => (
+              <div key={err.path ?? ''} className="text-small text-danger">
+                {err.path ? `${err.path} isn't watched for changes: ${err.message}` : err.message}
+              </div>
+            ))}
+          </div>
+        )}
+
         <Button
           variant="flat"
           radius="sm"
@@ -137,6 +183,7 @@ export function SettingsScreen() {
             const selected = await open({ directory: true })
             if (selected) await setDirs([...(queryDirs.data ?? []), selected])
             await queryDirs.refetch()
+            await queryWatchErrors.refetch()
           }}>
           <PlusIcon className="text-lg" /> Add Folder
         </Button>
@@ -150,6 +197,36 @@ export function SettingsScreen() {
           <FileScanIcon className="text-lg" /> Scan
         </Button>
 
+        {!mutationScan.isPending && !!queryScanErrors.data?.length && (
+          <div className="flex items-center gap-3">
+            <div className="text-small text-danger">{queryScanErrors.data.length} files failed in the last scan.</div>
+
+            <Button
+              size="sm"
+              radius="sm"
+              variant="flat"
+              isLoading={mutationRetryScan.isPending}
+              onPress={() => mutationRetryScan.mutate()}>
+              Retry
+            </Button>
+          </div>
+        )}
+
+        {mutationScan.isPending && (
+          <div className="flex items-center gap-3">
+            {scanProgress && (
+              <div className="text-small text-default-500">
+                Processed {scanProgress.processed} of {scanProgress.discovered} files with {scanProgress.errors}{' '}
+                errors.
+              </div>
+            )}
+
+            <Button size="sm" radius="sm" variant="flat" color="danger" onPress={cancelScan}>
+              Cancel
+            </Button>
+          </div>
+        )}
+
         <hr className="w-full mt-3 border-default/30" />
         <div className="text-large my-2">Appearance</div>
 
@@ -184,6 +261,104 @@ export function SettingsScreen() {
           ))}
         </Select>
 
+        <hr className="w-full mt-3 border-default/30" />
+        <div className="text-large my-2">Playback</div>
+
+        <Select
+          label="Output Device"
+          radius="sm"
+          labelPlacement="outside"
+          popoverProps={{ classNames: { content: 'rounded-small' } }}
+          classNames={{ base: 'w-64 mb-2', trigger: 'dark:bg-default/30 dark:hover:bg-default/40', listbox: 'px-0' }}
+          selectedKeys={[state.outputDevice ?? SYSTEM_OUTPUT]}
+          onOpenChange={isOpen => isOpen && queryOutputDevices.refetch()}
+          onSelectionChange={value => {
+            const key = value.currentKey
+            if (!key) return
+
+            store.setState({ outputDevice: key === SYSTEM_OUTPUT ? null : key })
+          }}>
+          {[
+            <SelectItem key={SYSTEM_OUTPUT}>System Default</SelectItem>,
+            ...(queryOutputDevices.data ?? []).map(device => (
+              <SelectItem key={device.name}>{device.isDefault ? `${device.name} (Default)` : device.name}</SelectItem>
+            )),
+          ]}
+        </Select>
+
+        <Select
+          label="Loudness Normalization"
+          radius="sm"
+          labelPlacement="outside"
+          popoverProps={{ classNames: { content: 'rounded-small' } }}
+          classNames={{ base: 'w-64 mb-2', trigger: 'dark:bg-default/30 dark:hover:bg-default/40', listbox: 'px-0' }}
+          selectedKeys={[state.normalization.mode]}
+          onSelectionChange={value => {
+            const mode = value.currentKey as NormalizationMode | undefined
+            if (!mode) return
+
+            store.setState(state => ({ normalization: { ...state.normalization, mode } }))
+          }}>
+          {Object.entries(NORMALIZATION_MODES).map(([mode, label]) => (
+            <SelectItem key={mode}>{label}</SelectItem>
+          ))}
+        </Select>
+
+        <Slider
+          size="sm"
+          label="Preamp"
+          color="foreground"
+          minValue={-15}
+          maxValue={15}
+          step={0.5}
+          getValue={value => `${value} dB`}
+          classNames={{ base: 'w-64 mb-2', labelWrapper: 'mb-1' }}
+          isDisabled={state.normalization.mode === 'off'}
+          value={preamp}
+          onChangeEnd={() => store.setState(state => ({ normalization: { ...state.normalization, preamp } }))}
+          onChange={value => setPreamp(typeof value === 'number' ? value : value[0])}
+        />
+
+        <Slider
+          size="sm"
+          label="Crossfade"
+          color="foreground"
+          minValue={0}
+          maxValue={12}
+          step={0.5}
+          getValue={value => (value ? `${value}s` : 'Off')}
+          classNames={{ base: 'w-64 mb-2', labelWrapper: 'mb-1' }}
+          value={crossfadeLength}
+          onChangeEnd={() =>
+            store.setState(state => ({ crossfade: { ...state.crossfade, length: crossfadeLength } }))
+          }
+          onChange={value => setCrossfadeLength(typeof value === 'number' ? value : value[0])}
+        />
+
+        <Select
+          label="Fade Curve"
+          radius="sm"
+          labelPlacement="outside"
+          popoverProps={{ classNames: { content: 'rounded-small' } }}
+          classNames={{ base: 'w-64', trigger: 'dark:bg-default/30 dark:hover:bg-default/40', listbox: 'px-0' }}
+          isDisabled={!state.crossfade.length}
+          selectedKeys={[state.crossfade.curve]}
+          onSelectionChange={value => {
+            const curve = value.currentKey as FadeCurve | undefined
+            if (!curve) return
+
+            store.setState(state => ({ crossfade: { ...state.crossfade, curve } }))
+          }}>
+          {Object.entries(FADE_CURVES).map(([curve, label]) => (
+            <SelectItem key={curve}>{label}</SelectItem>
+          ))}
+        </Select>
+
+        <hr className="w-full mt-3 border-default/30" />
+        <div className="text-large my-2">Equalizer</div>
+
+        <EqualizerSettings />
+
         <hr className="w-full mt-3 border-default/30" />
 
         <Accordion className="px-0" defaultExpandedKeys={['list']}>
@@ -313,14 +488,59 @@ export function SettingsScreen() {
   )
 }
 
+type ScanError = {
+  kind: 'unsupportedCodec' | 'io' | 'missingExtension' | 'probe' | 'coverWrite'
+  path: string
+  message: string
+}
+
+export type WatchError = {
+  path?: string | null
+  message: string
+}
+
+type ScanSummary = {
+  added: number
+  updated: number
+  removed: number
+  errors: ScanError[]
+}
+
+type ScanProgress = {
+  discovered: number
+  processed: number
+  errors: number
+  current?: string | null
+}
+
 async function scanDirs() {
-  return await invoke<string>('db_scan_dirs')
+  return await invoke<ScanSummary>('db_scan_dirs')
+}
+
+async function cancelScan() {
+  return await invoke('db_cancel_scan')
+}
+
+async function getScanErrors() {
+  return await invoke<ScanError[]>('db_get_scan_errors')
+}
+
+async function getWatchErrors() {
+  return await invoke<WatchError[]>('db_get_watch_errors')
+}
+
+async function retryScanErrors() {
+  return await invoke<ScanSummary>('db_retry_scan_errors')
 }
 
 async function setDirs(dirs: string[]) {
   return await invoke('db_set_dirs', { dirs })
 }
 
+async function getOutputDevices() {
+  return await invoke<OutputDevice[]>('player_get_output_devices')
+}
+
 async function getDirs() {
   return await invoke<string[]>('db_get_dirs')
 }
@@ -344,8 +564,22 @@ type Store = {
   fontFamily: string
   fontSize: number
   volume: number
+  // `null` follows the system default
+  outputDevice: string | null
+  normalization: Normalization
+  crossfade: Crossfade
+  // name of the preset for tracks without one of their own, `null` plays them flat
+  equalizer: string | null
 }
 
+type NormalizationMode = keyof typeof NORMALIZATION_MODES
+type Normalization = { mode: NormalizationMode; preamp: number; preventClipping: boolean }
+
+type OutputDevice = { name: string; isDefault: boolean }
+
+type FadeCurve = keyof typeof FADE_CURVES
+type Crossfade = { length: number; curve: FadeCurve }
+
 // had to put this in a fn because false is not assignable to boolean? WTF?
 function initialState(): Store {
   return {
@@ -355,6 +589,10 @@ function initialState(): Store {
     fontFamily: 'Poppins',
     fontSize: 16,
     volume: 1,
+    outputDevice: null,
+    normalization: { mode: 'off', preamp: 0, preventClipping: true },
+    crossfade: { length: 0, curve: 'equalPower' },
+    equalizer: null,
   }
 }
 
@@ -373,6 +611,10 @@ export function setMiniPlayerVisibility(isVisible: boolean) {
   store.setState({ isMiniPlayerVisible: isVisible })
 }
 
+export function setGlobalVolume(volume: number) {
+  store.setState({ volume })
+}
+
 export async function setEmotion(name: string) {
   store.setState({ currentEmotion: name })
 }
@@ -388,6 +630,28 @@ function applyTheme({ fontFamily, fontSize }: Store = store.getState()) {
 export async function init(state = store.getState()) {
   applyTheme(state)
   await setPlayerVolume(state.volume)
+  await setNormalization(state.normalization)
+  await setCrossfade(state.crossfade)
+  await setDefaultEqualizer(state.equalizer)
+  await setOutputDevice(state.outputDevice)
+}
+
+// reopening a device interrupts playback, so only when it actually changed
+let appliedOutputDevice: string | null = null
+
+async function setOutputDevice(device: string | null) {
+  if (device === appliedOutputDevice) return
+
+  appliedOutputDevice = device
+  return await invoke('player_set_output_device', { device })
+}
+
+async function setNormalization(normalization: Normalization) {
+  return await invoke('player_set_normalization', { normalization })
+}
+
+async function setCrossfade(crossfade: Crossfade) {
+  return await invoke('player_set_crossfade', { crossfade })
 }
 
 function KeyBindings() {
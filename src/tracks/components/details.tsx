@@ -33,7 +33,7 @@ import {
   UserRoundIcon,
 } from 'lucide-react'
 import { getAssetUrl } from '@/utils'
-import { normalizeMeta } from '@/tracks'
+import { getDuration, normalizeMeta } from '@/tracks'
 import {
   PlainLyricsView,
   SyncedLyricsView,
@@ -45,6 +45,8 @@ import {
 import { useScrubPlayer } from '@/scrub-player'
 import { ScrubPlayer } from '@/scrub-player/components'
 import { RulesEditor, setRules, validateRules, useExecuteRules } from '@/rules'
+import { TrackEqualizer } from '@/equalizer/components'
+import { TagsEditor } from '@/tracks/components/tags'
 import type { LucideIcon } from 'lucide-react'
 import type { Track } from '@/tracks'
 import type { Lyrics } from '@/lyrics'
@@ -60,7 +62,7 @@ export function TrackDetailsModal() {
 
         <ModalBody className="flex flex-col gap-3">
           <div className="flex w-full gap-3">
-            <Cover url={data?.cover} className="size-60 shrink-0" />
+            <Cover url={data?.cover} size={240} className="size-60 shrink-0" />
 
             <div className="flex flex-col gap-2">
               {meta.title && <div className="text-large">{meta.title}</div>}
@@ -105,20 +107,28 @@ async function getTrack(hash: string) {
 
 type CoverProps = {
   url?: string | null
+  // about how many css pixels it's shown at, picks a thumbnail instead of the full image
+  size?: number
   className?: string
   placeholder?: LucideIcon | (() => React.ReactNode)
   external?: boolean
   onClick?: () => void
 }
 
-export function Cover({ url, className, placeholder: Placeholder = MusicIcon, external, onClick }: CoverProps) {
+export function Cover({ url, size, className, placeholder: Placeholder = MusicIcon, external, onClick }: CoverProps) {
   const Component = onClick ? 'button' : 'div'
+  const [isThumbnailMissing, setIsThumbnailMissing] = useState(false)
+
+  useEffect(() => setIsThumbnailMissing(false), [url])
+
+  // covers scanned before thumbnails existed don't have them until the next scan
+  const src = url && (external ? url : getAssetUrl(size && !isThumbnailMissing ? getThumbnailPath(url, size) : url))
 
   return (
     <Component
       {...(onClick && { type: 'button', onClick, disabled: !onClick })}
       className={cn('rounded-small overflow-hidden', onClick && 'cursor-pointer', className)}>
-      {url ? (
+      {src ? (
         <Image
           isBlurred
           radius="none"
@@ -126,7 +136,8 @@ export function Cover({ url, className, placeholder: Placeholder = MusicIcon, ex
           width="100%"
           height="100%"
           loading="lazy"
-          src={external ? url : getAssetUrl(url)}
+          src={src}
+          onError={() => setIsThumbnailMissing(true)}
           classNames={{ wrapper: 'size-full', img: 'size-full object-contain' }}
         />
       ) : (
@@ -138,6 +149,15 @@ export function Cover({ url, className, placeholder: Placeholder = MusicIcon, ex
   )
 }
 
+// mirrors `THUMBNAIL_SIZES` in the backend, longest side in pixels
+const THUMBNAIL_SIZES = [128, 512]
+
+// e.g. "{hash}.png" -> "{hash}-128.jpg", the full image when none is big enough
+function getThumbnailPath(path: string, size: number) {
+  const thumbnail = THUMBNAIL_SIZES.find(x => x >= size * window.devicePixelRatio)
+  return thumbnail ? path.replace(/\.[^./\\]+$/, `-${thumbnail}.jpg`) : path
+}
+
 type PropertyTextProps = { link?: string; children: React.ReactNode; className?: string; onClick?: () => void }
 
 export function PropertyText({ link, children, className, onClick }: PropertyTextProps) {
@@ -182,11 +202,40 @@ export function MoreDetails({ data, className }: MoreDetailsProps) {
       <Pair label="File Path" value={data?.path} />
       <Pair label="Hash" value={data?.hash} />
       <Pair label="Extension" value={data?.extension} />
+      <Pair label="Codec" value={data?.codec?.toUpperCase()} />
+      <Pair label="Duration" value={data && `${(data.duration / 1000).toFixed(3)}s`} />
+      <Pair label="Sample Rate" value={data?.sampleRate && `${data.sampleRate / 1000} kHz`} />
+      <Pair label="Bit Depth" value={data?.bitDepth && `${data.bitDepth}-bit`} />
+      <Pair label="Channels" value={formatChannels(data?.channels)} />
+      <Pair label="Bitrate" value={data?.bitrate && `${data.bitrate} kbps`} />
+      <Pair label="Track" value={formatOf(data?.trackNumber, data?.trackTotal)} />
+      <Pair label="Disc" value={formatOf(data?.discNumber, data?.discTotal)} />
+      <Pair label="Composer" value={data?.composer} />
+      <Pair label="Conductor" value={data?.conductor} />
+      <Pair label="BPM" value={data?.bpm} />
+      <Pair label="Label" value={data?.label} />
+      <Pair label="ISRC" value={data?.isrc} />
+      <Pair label="Comment" value={data?.comment} />
+      {data?.compilation && <Pair label="Compilation" value="Yes" />}
+      <Pair label="MusicBrainz Track" value={data?.musicbrainzTrackId} />
+      <Pair label="MusicBrainz Album" value={data?.musicbrainzAlbumId} />
       {data?.rank && <Pair label="Emotion Rank" value={data.rank} />}
     </div>
   )
 }
 
+function formatChannels(channels?: number | null) {
+  if (channels === 1) return 'Mono'
+  if (channels === 2) return 'Stereo'
+  return channels
+}
+
+// e.g. "3 / 12", or just the number when the total isn't known
+function formatOf(number?: number | null, total?: number | null) {
+  if (number == null) return null
+  return total ? `${number} / ${total}` : number
+}
+
 type PairProps = { label: string; value?: string | number | null }
 
 function Pair({ label, value }: PairProps) {
@@ -305,10 +354,12 @@ export function TrackScreen() {
             <Tabs variant="underlined" selectedKey={tab} onSelectionChange={key => setTab(key as string)}>
               <Tab key="lyrics" title="Lyrics" />
               <Tab key="rules" title="Rules" />
+              <Tab key="equalizer" title="Equalizer" />
+              <Tab key="tags" title="Tags" />
               <Tab key="more" title="More Details" />
             </Tabs>
 
-            {tab[0] !== 'm' && <div className="h-5 border-r border-default/30 mr-3" />}
+            {(tab[0] === 'l' || tab[0] === 'r') && <div className="h-5 border-r border-default/30 mr-3" />}
 
             {tab[0] === 'l' && (
               <>
@@ -392,7 +443,7 @@ export function TrackScreen() {
                   <SyncedLyricsView
                     id={selectedLyrics.id}
                     data={selectedLyrics.synced}
-                    duration={query.data?.duration}
+                    duration={getDuration(query.data)}
                     elapsed={player.elapsed}
                     onSeek={player.seek}
                     className="size-full"
@@ -407,6 +458,10 @@ export function TrackScreen() {
             </div>
           )}
 
+          {tab[0] === 'e' && query.data && <TrackEqualizer track={query.data} className="px-6" />}
+
+          {tab[0] === 't' && query.data && <TagsEditor track={query.data} className="px-6 pb-3" />}
+
           {tab[0] === 'm' && <MoreDetails data={query.data} className="px-6" />}
         </div>
       )}
@@ -18,15 +18,17 @@ import {
   Disc3Icon,
   GripVerticalIcon,
   ListMusicIcon,
+  ListStartIcon,
   ListVideoIcon,
   MoveLeftIcon,
   PlayIcon,
   PlusIcon,
+  TagsIcon,
   UserRoundIcon,
 } from 'lucide-react'
 import { useDebounce } from 'use-debounce'
 import { useSelection } from '@/utils'
-import { extendQueue, usePlayer } from '@/player'
+import { extendQueue, insertNext, usePlayer } from '@/player'
 import { setMiniPlayerVisibility, setPlayerMaximized } from '@/settings'
 import { addPlaylist, addPlaylistTracks, getPlaylists } from '@/playlists'
 import { SearchBar, SelectAllControls, AppBar } from '@/components'
@@ -34,6 +36,7 @@ import { SortableVirtualList } from '@/components/lists'
 import { PlaylistEditorModal } from '@/playlists/components'
 import { createSearchIndex, getTracks, normalizeMeta } from '@/tracks'
 import { AlbumLink, ArtistLink, Cover, PropertyText, useTrackDetails } from '@/tracks/components/details'
+import { BatchTagsModal } from '@/tracks/components/tags'
 import type { Track } from '@/tracks'
 import type {
   DraggableProps,
@@ -90,6 +93,7 @@ export function TracksScreen() {
 
   const selection = useTrackSelection()
   const playlistEditorModal = useDisclosure()
+  const batchTagsModal = useDisclosure()
 
   const queryPlaylists = useQuery({ queryKey: ['playlists'], queryFn: getPlaylists })
   const playlists = queryPlaylists.data ?? []
@@ -120,6 +124,16 @@ export function TracksScreen() {
               <ListVideoIcon className="text-lg" /> Add to Queue
             </Button>
 
+            <Button
+              radius="sm"
+              variant="flat"
+              onPress={async () => {
+                await insertNext(selection.values)
+                selection.clear()
+              }}>
+              <ListStartIcon className="text-lg" /> Play Next
+            </Button>
+
             <Dropdown radius="sm" backdrop="opaque">
               <DropdownTrigger>
                 <Button radius="sm" variant="flat">
@@ -153,6 +167,10 @@ export function TracksScreen() {
                 </DropdownSection>
               </DropdownMenu>
             </Dropdown>
+
+            <Button radius="sm" variant="flat" onPress={batchTagsModal.onOpen}>
+              <TagsIcon className="text-lg" /> Edit Tags
+            </Button>
           </>
         ) : (
           <>
@@ -232,6 +250,17 @@ export function TracksScreen() {
           navigate(`/playlists/${name}`)
         }}
       />
+
+      <BatchTagsModal
+        // as listed, so renumbering follows what's on screen
+        tracks={filtered.filter(selection.isSelected)}
+        isOpen={batchTagsModal.isOpen}
+        onOpenChange={batchTagsModal.onOpenChange}
+        onApply={() => {
+          batchTagsModal.onClose()
+          selection.clear()
+        }}
+      />
     </div>
   )
 }
@@ -310,6 +339,7 @@ export const ListItem = memo(
 
         <Cover
           url={data.cover}
+          size={64}
           onClick={() => onShowDetails?.(data)}
           className={cn('size-16 mx-3', onShowDetails && 'cursor-pointer')}
         />
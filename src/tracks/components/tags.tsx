@@ -0,0 +1,390 @@
+import { useEffect, useState } from 'react'
+import { useMutation, useQuery, useQueryClient } from '@tanstack/react-query'
+import { open } from '@tauri-apps/plugin-dialog'
+import {
+  Button,
+  Input,
+  Modal,
+  ModalBody,
+  ModalContent,
+  ModalFooter,
+  ModalHeader,
+  ScrollShadow,
+  Tab,
+  Tabs,
+  addToast,
+  cn,
+} from '@heroui/react'
+import { CheckIcon, EyeIcon, ImageIcon, ImageOffIcon, SaveIcon, Undo2Icon, UndoIcon } from 'lucide-react'
+import { applyTagEdit, getTagEdits, previewTagEdit, undoTagEdit, updateTags } from '@/tracks'
+import { Cover } from '@/tracks/components/details'
+import type { BatchOperation, TagChange, TagEditResult, Track, TagUpdate } from '@/tracks'
+
+const FIELDS = [
+  ['title', 'Title'],
+  ['artist', 'Artist'],
+  ['album', 'Album'],
+  ['albumArtist', 'Album Artist'],
+  ['date', 'Date'],
+  ['genre', 'Genre'],
+  ['trackNumber', 'Track Number'],
+] as const
+
+type Field = (typeof FIELDS)[number][0]
+type Values = Record<Field, string>
+
+type TagsEditorProps = { track: Track; className?: string }
+
+export function TagsEditor({ track, className }: TagsEditorProps) {
+  const queryClient = useQueryClient()
+  const [values, setValues] = useState(() => getValues(track))
+  // `undefined` keeps the embedded one, `null` removes it
+  const [cover, setCover] = useState<string | null>()
+
+  useEffect(() => {
+    setValues(getValues(track))
+    setCover(undefined)
+  }, [track])
+
+  const update = getUpdate(track, values, cover)
+  const isChanged = Object.keys(update).length > 0
+
+  const mutationSave = useMutation({
+    mutationFn: async () => await updateTags(track, update),
+    onSuccess: async () => {
+      await queryClient.invalidateQueries()
+      addToast({ color: 'success', title: 'Tags Saved' })
+    },
+    onError: err => addToast({ color: 'danger', title: 'Tags', description: err.message }),
+  })
+
+  return (
+    <div className={cn('flex gap-6 items-start', className)}>
+      <div className="flex flex-col gap-2 shrink-0">
+        <Cover url={cover === undefined ? track.cover : cover} className="size-48" />
+
+        <Button
+          size="sm"
+          radius="sm"
+          variant="flat"
+          onPress={async () => {
+            const selected = await open({ filters: [{ name: 'Image', extensions: ['jpg', 'jpeg', 'png'] }] })
+            if (selected) setCover(selected)
+          }}>
+          <ImageIcon className="text-medium" /> Choose Cover
+        </Button>
+
+        <Button
+          size="sm"
+          radius="sm"
+          variant="flat"
+          isDisabled={cover === null || (cover === undefined && !track.cover)}
+          onPress={() => setCover(null)}>
+          <ImageOffIcon className="text-medium" /> Remove Cover
+        </Button>
+      </div>
+
+      <div className="grid grid-cols-2 gap-3 w-120">
+        {FIELDS.map(([field, label]) => (
+          <Input
+            key={field}
+            size="sm"
+            radius="sm"
+            label={label}
+            variant="flat"
+            type={field === 'trackNumber' ? 'number' : 'text'}
+            value={values[field]}
+            onValueChange={value => setValues({ ...values, [field]: value })}
+          />
+        ))}
+
+        <div className="col-span-2 flex gap-2">
+          <Button
+            size="sm"
+            radius="sm"
+            variant="flat"
+            isDisabled={!isChanged}
+            isLoading={mutationSave.isPending}
+            onPress={() => mutationSave.mutate()}>
+            <SaveIcon className="text-medium" /> Save Tags
+          </Button>
+
+          <Button
+            size="sm"
+            radius="sm"
+            variant="light"
+            isDisabled={!isChanged || mutationSave.isPending}
+            onPress={() => {
+              setValues(getValues(track))
+              setCover(undefined)
+            }}>
+            <UndoIcon className="text-medium" /> Discard
+          </Button>
+        </div>
+      </div>
+    </div>
+  )
+}
+
+function getValues(track: Track): Values {
+  return {
+    title: track.title ?? '',
+    artist: track.artist ?? '',
+    album: track.album ?? '',
+    albumArtist: track.albumArtist ?? '',
+    date: track.date ?? '',
+    genre: track.genre ?? '',
+    trackNumber: track.trackNumber?.toString() ?? '',
+  }
+}
+
+// only what differs from the track is sent, blank fields remove the tag
+function getUpdate(track: Track, values: Values, cover?: string | null) {
+  const initial = getValues(track)
+  const update: Record<string, string | number | null> = {}
+
+  for (const [field] of FIELDS) {
+    const value = values[field].trim()
+    if (value === initial[field]) continue
+
+    if (field !== 'trackNumber') update[field] = value || null
+    else update[field] = parseInt(value) > 0 ? parseInt(value) : null
+  }
+
+  if (cover !== undefined) update.cover = cover
+
+  return update as TagUpdate
+}
+
+const PATTERN_FIELDS = '%track% %title% %artist% %album% %albumartist% %date% %genre%'
+
+type BatchKind = BatchOperation['kind']
+
+type BatchTagsModalProps = {
+  // in the order they're renumbered
+  tracks: Track[]
+  isOpen: boolean
+  onOpenChange: (isOpen: boolean) => void
+  onApply?: () => void
+}
+
+export function BatchTagsModal({ tracks, isOpen, onOpenChange, onApply }: BatchTagsModalProps) {
+  const queryClient = useQueryClient()
+  const [kind, setKind] = useState<BatchKind>('set')
+  const [values, setValues] = useState<Partial<Values>>({})
+  const [start, setStart] = useState('1')
+  const [pattern, setPattern] = useState('%track% - %artist% - %title%')
+  const [preview, setPreview] = useState<TagChange[] | null>(null)
+
+  const operation = getOperation(kind, values, start, pattern)
+  const byHash = new Map(tracks.map(t => [t.hash, t]))
+
+  // anything changed after the preview needs a new one before it can be applied
+  useEffect(() => setPreview(null), [kind, values, start, pattern, tracks.map(t => t.hash).join()])
+
+  const queryEdits = useQuery({ queryKey: ['tag-edits'], queryFn: getTagEdits, enabled: isOpen })
+  const lastEdit = queryEdits.data?.at(0)
+
+  const onResult = async (title: string, result: TagEditResult) => {
+    setPreview(null)
+    await queryClient.invalidateQueries()
+
+    if (!result.failed.length) return addToast({ color: 'success', title, description: `${result.applied} files` })
+
+    addToast({
+      color: 'warning',
+      title,
+      description: `${result.applied} files, ${result.failed.length} failed: ${result.failed[0].error}`,
+    })
+  }
+
+  const mutationPreview = useMutation({
+    mutationFn: async () => await previewTagEdit(tracks, operation!),
+    onSuccess: setPreview,
+    onError: err => addToast({ color: 'danger', title: 'Preview', description: err.message }),
+  })
+
+  const mutationApply = useMutation({
+    mutationFn: async () => await applyTagEdit(tracks, operation!),
+    onSuccess: async result => {
+      await onResult('Tags Applied', result)
+      onApply?.()
+    },
+    onError: err => addToast({ color: 'danger', title: 'Tags', description: err.message }),
+  })
+
+  const mutationUndo = useMutation({
+    mutationFn: undoTagEdit,
+    onSuccess: result => onResult('Undone', result),
+    onError: err => addToast({ color: 'danger', title: 'Undo', description: err.message }),
+  })
+
+  const applicable = preview?.filter(x => !x.error).length ?? 0
+
+  return (
+    <Modal
+      isOpen={isOpen}
+      placement="bottom-center"
+      backdrop="blur"
+      radius="sm"
+      size="3xl"
+      scrollBehavior="inside"
+      onOpenChange={onOpenChange}>
+      <ModalContent>
+        <ModalHeader>Edit Tags of {tracks.length} Tracks</ModalHeader>
+
+        <ModalBody className="flex flex-col gap-3">
+          <Tabs variant="underlined" selectedKey={kind} onSelectionChange={key => setKind(key as BatchKind)}>
+            <Tab key="set" title="Set Tags" />
+            <Tab key="renumber" title="Renumber" />
+            <Tab key="fromFilename" title="Tags from File Names" />
+            <Tab key="toFilename" title="File Names from Tags" />
+          </Tabs>
+
+          {kind === 'set' && (
+            <div className="grid grid-cols-2 gap-3">
+              {FIELDS.filter(([field]) => field !== 'trackNumber').map(([field, label]) => (
+                <Input
+                  key={field}
+                  size="sm"
+                  radius="sm"
+                  label={label}
+                  variant="flat"
+                  placeholder="Left as it is"
+                  value={values[field] ?? ''}
+                  onValueChange={value => setValues({ ...values, [field]: value })}
+                />
+              ))}
+            </div>
+          )}
+
+          {kind === 'renumber' && (
+            <Input
+              size="sm"
+              radius="sm"
+              type="number"
+              label="Start From"
+              variant="flat"
+              className="w-48"
+              value={start}
+              onValueChange={setStart}
+              description="In the order the tracks are listed"
+            />
+          )}
+
+          {(kind === 'fromFilename' || kind === 'toFilename') && (
+            <Input
+              size="sm"
+              radius="sm"
+              label="Pattern"
+              variant="flat"
+              value={pattern}
+              onValueChange={setPattern}
+              classNames={{ input: 'font-mono' }}
+              description={`File names without the extension, using ${PATTERN_FIELDS}`}
+            />
+          )}
+
+          {preview && (
+            <ScrollShadow className="flex flex-col gap-2 max-h-80">
+              {!preview.length && <div className="text-default-500 text-small">Nothing would change</div>}
+
+              {preview.map(change => (
+                <ChangePreview key={change.hash} change={change} track={byHash.get(change.hash)} />
+              ))}
+            </ScrollShadow>
+          )}
+        </ModalBody>
+
+        <ModalFooter>
+          {lastEdit && (
+            <Button
+              radius="sm"
+              variant="light"
+              className="mr-auto"
+              isLoading={mutationUndo.isPending}
+              onPress={() => mutationUndo.mutate()}>
+              <Undo2Icon className="text-lg" /> Undo "{lastEdit.summary}"
+            </Button>
+          )}
+
+          <Button
+            radius="sm"
+            variant="flat"
+            isDisabled={!operation || !tracks.length}
+            isLoading={mutationPreview.isPending}
+            onPress={() => mutationPreview.mutate()}>
+            <EyeIcon className="text-lg" /> Preview
+          </Button>
+
+          <Button
+            radius="sm"
+            variant="flat"
+            color="success"
+            isDisabled={!applicable}
+            isLoading={mutationApply.isPending}
+            onPress={() => mutationApply.mutate()}>
+            <CheckIcon className="text-lg" /> Apply to {applicable} Files
+          </Button>
+        </ModalFooter>
+      </ModalContent>
+    </Modal>
+  )
+}
+
+type ChangePreviewProps = { change: TagChange; track?: Track }
+
+function ChangePreview({ change, track }: ChangePreviewProps) {
+  const values = track && getValues(track)
+
+  return (
+    <div className="flex flex-col gap-1 text-tiny rounded-small bg-default-50/50 px-3 py-2">
+      <div className="text-default-700 break-all">{track?.name ?? change.path}</div>
+
+      {FIELDS.filter(([field]) => field in change.update).map(([field, label]) => (
+        <div key={field} className="flex gap-2 text-default-500">
+          <div className="w-28 shrink-0">{label}</div>
+          <div className="line-through">{values?.[field] || '-'}</div>
+          <div>→</div>
+          <div className="text-default-700">{change.update[field] ?? '-'}</div>
+        </div>
+      ))}
+
+      {change.rename && (
+        <div className="flex gap-2 text-default-500">
+          <div className="w-28 shrink-0">File Name</div>
+          <div className="text-default-700 break-all">{getFileName(change.rename)}</div>
+        </div>
+      )}
+
+      {change.error && <div className="text-danger-500">{change.error}</div>}
+    </div>
+  )
+}
+
+// `null` while there's not enough to go with
+function getOperation(kind: BatchKind, values: Partial<Values>, start: string, pattern: string): BatchOperation | null {
+  switch (kind) {
+    case 'set': {
+      const update = Object.fromEntries(
+        Object.entries(values)
+          .map(([field, value]) => [field, (value ?? '').trim()])
+          .filter(([, value]) => value),
+      )
+
+      return Object.keys(update).length ? { kind, update } : null
+    }
+
+    case 'renumber': {
+      const value = parseInt(start)
+      return value > 0 ? { kind, start: value } : null
+    }
+
+    default:
+      return pattern.includes('%') ? { kind, pattern } : null
+  }
+}
+
+function getFileName(path: string) {
+  return path.split(/[\\/]/).at(-1)
+}
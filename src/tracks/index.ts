@@ -7,7 +7,14 @@ export type Track = {
   path: string
   name: string
   extension: string
+  // milliseconds
   duration: number
+  sampleRate?: number | null
+  bitDepth?: number | null
+  channels?: number | null
+  codec?: string | null
+  // kbps
+  bitrate?: number | null
   cover?: string | null
   title?: string | null
   artist?: string | null
@@ -15,6 +22,27 @@ export type Track = {
   albumArtist?: string | null
   date?: string | null
   genre?: string | null
+  trackNumber?: number | null
+  trackTotal?: number | null
+  discNumber?: number | null
+  discTotal?: number | null
+  composer?: string | null
+  conductor?: string | null
+  bpm?: number | null
+  comment?: string | null
+  label?: string | null
+  isrc?: string | null
+  musicbrainzTrackId?: string | null
+  musicbrainzAlbumId?: string | null
+  musicbrainzArtistId?: string | null
+  musicbrainzAlbumArtistId?: string | null
+  musicbrainzReleaseGroupId?: string | null
+  sortTitle?: string | null
+  sortArtist?: string | null
+  sortAlbum?: string | null
+  sortAlbumArtist?: string | null
+  sortComposer?: string | null
+  compilation?: boolean
   position?: number | null
   rank?: number | null
   rules?: string | null
@@ -39,9 +67,58 @@ export async function getArtists() {
   return await invoke<string[]>('db_get_artists')
 }
 
+// a missing field is left as it is in the file, `null` removes it
+export type TagUpdate = Partial<
+  Pick<Track, 'title' | 'artist' | 'album' | 'albumArtist' | 'date' | 'genre' | 'trackNumber'>
+> & {
+  // path of an image to embed as the front cover
+  cover?: string | null
+}
+
+// writes into the file itself, MP3, FLAC, Ogg and M4A only
+export async function updateTags(track: Track, update: TagUpdate) {
+  return await invoke<Track>('tracks_update_tags', { hash: track.hash, update })
+}
+
+export type BatchOperation =
+  | { kind: 'set'; update: TagUpdate }
+  // in the order the tracks are given
+  | { kind: 'renumber'; start: number }
+  // e.g. "%track% - %artist% - %title%"
+  | { kind: 'fromFilename'; pattern: string }
+  | { kind: 'toFilename'; pattern: string }
+
+// `error` is why the file is skipped
+export type TagChange = { hash: string; path: string; update: TagUpdate; rename?: string; error?: string }
+export type TagEditResult = { applied: number; failed: TagChange[] }
+export type TagEdit = { id: number; summary: string }
+
+// the dry run, tracks that would stay the same are left out
+export async function previewTagEdit(tracks: Track[], operation: BatchOperation) {
+  return await invoke<TagChange[]>('tracks_preview_tag_edit', { hashes: tracks.map(t => t.hash), operation })
+}
+
+export async function applyTagEdit(tracks: Track[], operation: BatchOperation) {
+  return await invoke<TagEditResult>('tracks_apply_tag_edit', { hashes: tracks.map(t => t.hash), operation })
+}
+
+// newest first, only the newest can be undone
+export async function getTagEdits() {
+  return await invoke<TagEdit[]>('tracks_get_tag_edits')
+}
+
+export async function undoTagEdit() {
+  return await invoke<TagEditResult>('tracks_undo_tag_edit')
+}
+
+// the players count whole seconds
+export function getDuration(track?: Track | null) {
+  return Math.floor((track?.duration ?? 0) / 1000)
+}
+
 export function normalizeMeta(track?: Track | null) {
   return {
-    duration: formatTime(track?.duration),
+    duration: formatTime(getDuration(track)),
     title: track?.title ?? track?.name,
     artist: track?.artist,
     album: track?.album,
@@ -10,7 +10,8 @@ export function getAssetUrl(path: string) {
 export function formatTime(value?: number | null): string {
   if (value == null || isNaN(value) || value <= 0) return '0:00'
 
-  const rounded = value // Math.floor(value) // already whole seconds
+  // positions from the backend are fractional
+  const rounded = Math.floor(value)
   const mins = Math.floor(rounded / 60)
   const secs = rounded % 60
 